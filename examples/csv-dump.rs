@@ -0,0 +1,129 @@
+//! Renders a scripted sequence of parameter changes and prints the output
+//! samples as CSV, to diff against the JavaScript Pink Trombone fed the
+//! same script.
+//!
+//! Usage: `cargo run --example csv-dump -- <script> <seconds> [seed]`
+//!
+//! Every non-empty script line not starting with `#` is an event:
+//! `<time in seconds> <parameter> <value>`, in ascending order of time.
+//! Parameters and their counterparts in the JavaScript code:
+//!
+//! | parameter         | JavaScript                  |
+//! |-------------------|-----------------------------|
+//! | `frequency`       | `Glottis.UIFrequency`       |
+//! | `tenseness`       | `Glottis.UITenseness`       |
+//! | `intensity`       | `Glottis.intensity`         |
+//! | `touched`         | `Glottis.isTouched` (0/1)   |
+//! | `always_voice`    | `alwaysVoice` (0/1)         |
+//! | `tongue_index`    | `TractUI.tongueIndex`       |
+//! | `tongue_diameter` | `TractUI.tongueDiameter`    |
+//! | `velum_open`      | `Tract.velumTarget` (0/1)   |
+//!
+//! Like the JavaScript audio callback, samples are synthesized in blocks of
+//! 512, and events take effect at the start of the first block beginning
+//! at or after their time.
+//!
+//! Aspiration and frication noise are silenced with `ConstantNoise(0.5)`,
+//! so the output only depends on the script and `seed` (default 0), which
+//! drives the pitch and tenseness drift. For a comparable JavaScript run,
+//! seed its simplex noise with the same value and make the white noise
+//! buffers constant 0 (`Math.random` returning 0.5).
+
+use std::io::{self, BufWriter, Write};
+
+use pink_trombone::{testing::ConstantNoise, NoiseMode, Params, PinkTrombone};
+
+const SAMPLE_RATE: u32 = 48000;
+const BLOCK_LEN: usize = 512;
+
+struct Event {
+    time: f64,
+    parameter: String,
+    value: f64,
+}
+
+fn parse_script(script: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parse = |field: &str| -> f64 {
+            field
+                .parse()
+                .unwrap_or_else(|_| panic!("line {}: invalid number {}", number + 1, field))
+        };
+        match fields.as_slice() {
+            [time, parameter, value] => events.push(Event {
+                time: parse(time),
+                parameter: parameter.to_string(),
+                value: parse(value),
+            }),
+            _ => panic!("line {}: expected <time> <parameter> <value>", number + 1),
+        }
+    }
+    events
+}
+
+fn apply(trombone: &mut PinkTrombone, event: &Event) {
+    let value = event.value;
+    let mut params = Params::default();
+    match event.parameter.as_str() {
+        "frequency" => params.target_frequency = Some(value as f32),
+        "tenseness" => params.target_tenseness = Some(value as f32),
+        "intensity" => params.intensity = Some(value as f32),
+        "tongue_index" => params.tongue_index = Some(value),
+        "tongue_diameter" => params.tongue_diameter = Some(value),
+        "velum_open" => params.velum_open = Some(value != 0.0),
+        "touched" => trombone.set_touched(value != 0.0),
+        "always_voice" => trombone.set_always_voice(value != 0.0),
+        other => panic!("unknown parameter {}", other),
+    }
+    trombone.set_params_pending(params);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("usage: csv-dump <script> <seconds> [seed]");
+        std::process::exit(2);
+    }
+    let script = std::fs::read_to_string(&args[1]).expect("can not read the script");
+    let events = parse_script(&script);
+    let seconds: f64 = args[2].parse().expect("invalid duration");
+    let seed: u16 = args
+        .get(3)
+        .map_or(0, |seed| seed.parse().expect("invalid seed"));
+
+    let mut trombone = PinkTrombone::with_noise_sources(
+        SAMPLE_RATE,
+        &mut ConstantNoise(0.5),
+        &mut ConstantNoise(0.5),
+        seed,
+        NoiseMode::default(),
+    );
+    let total = (seconds * SAMPLE_RATE as f64).round() as usize;
+    let mut block = [0.0; BLOCK_LEN];
+    let mut next_event = 0;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    writeln!(out, "time,output").unwrap();
+    let mut position = 0;
+    while position < total {
+        let block_time = position as f64 / SAMPLE_RATE as f64;
+        while next_event < events.len() && events[next_event].time <= block_time {
+            apply(&mut trombone, &events[next_event]);
+            next_event += 1;
+        }
+        let len = BLOCK_LEN.min(total - position);
+        trombone.fill(&mut block[..len]);
+        for (i, x) in block[..len].iter().enumerate() {
+            let time = (position + i) as f64 / SAMPLE_RATE as f64;
+            writeln!(out, "{:.6},{}", time, x).unwrap();
+        }
+        position += len;
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+/// Automatic gain control settings, see `PinkTrombone::set_agc`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AgcSettings {
+    /// Output RMS level to aim for.
+    pub target_rms: f32,
+    /// How fast the gain drops when the input gets louder.
+    pub attack: Duration,
+    /// How fast the gain recovers when the input gets quieter.
+    pub release: Duration,
+}
+
+impl Default for AgcSettings {
+    fn default() -> AgcSettings {
+        AgcSettings {
+            target_rms: 0.1,
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(300),
+        }
+    }
+}
+
+/// the gain is never raised above this, so silence is not amplified into noise
+const MAX_GAIN: f32 = 10.0;
+
+#[derive(Clone)]
+pub struct Agc {
+    settings: AgcSettings,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    mean_square: f32,
+}
+
+impl Agc {
+    /// Panics unless `settings.target_rms` is positive and finite.
+    pub fn new(settings: AgcSettings, sample_rate: u32) -> Agc {
+        if !(settings.target_rms.is_finite() && settings.target_rms > 0.0) {
+            panic!("AGC target_rms must be positive and finite");
+        }
+        let coefficient = |time: Duration| {
+            let samples = time.as_secs_f32() * sample_rate as f32;
+            if samples > 0.0 {
+                1.0 - (-1.0 / samples).exp()
+            } else {
+                1.0
+            }
+        };
+        Agc {
+            settings,
+            attack_coefficient: coefficient(settings.attack),
+            release_coefficient: coefficient(settings.release),
+            // start at unity gain
+            mean_square: settings.target_rms * settings.target_rms,
+        }
+    }
+
+    pub fn settings(&self) -> AgcSettings {
+        self.settings
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let power = x * x;
+        let coefficient = if power > self.mean_square {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.mean_square += coefficient * (power - self.mean_square);
+        let target = self.settings.target_rms;
+        let rms = self.mean_square.sqrt().max(target / MAX_GAIN);
+        x * target / rms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn tone(amplitude: f32, n: usize) -> f32 {
+        amplitude * (2.0 * std::f32::consts::PI * 440.0 * n as f32 / SAMPLE_RATE as f32).sin()
+    }
+
+    /// 440 Hz square wave, whose power does not fluctuate
+    fn square(amplitude: f32, n: usize) -> f32 {
+        amplitude * tone(1.0, n).signum()
+    }
+
+    fn rms(signal: &[f32]) -> f32 {
+        (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn converges_to_target() {
+        for amplitude in [0.5, 0.05] {
+            let mut agc = Agc::new(AgcSettings::default(), SAMPLE_RATE);
+            let output: Vec<f32> = (0..SAMPLE_RATE as usize * 2)
+                .map(|n| agc.process(square(amplitude, n)))
+                .collect();
+            let settled = rms(&output[output.len() - SAMPLE_RATE as usize / 10..]);
+            assert!(
+                (settled - 0.1).abs() < 0.001,
+                "{} for {}",
+                settled,
+                amplitude
+            );
+        }
+
+        // the level of a tone is tracked closer to its peaks
+        let mut agc = Agc::new(AgcSettings::default(), SAMPLE_RATE);
+        let output: Vec<f32> = (0..SAMPLE_RATE as usize * 2)
+            .map(|n| agc.process(tone(0.5, n)))
+            .collect();
+        let settled = rms(&output[output.len() - SAMPLE_RATE as usize / 10..]);
+        assert!(settled > 0.05 && settled < 0.1, "{}", settled);
+    }
+
+    #[test]
+    fn gain_is_capped_on_silence() {
+        let mut agc = Agc::new(AgcSettings::default(), SAMPLE_RATE);
+        for n in 0..SAMPLE_RATE as usize * 2 {
+            assert_eq!(agc.process(0.0), 0.0);
+            let quiet = tone(1e-4, n);
+            assert!(agc.process(quiet).abs() <= quiet.abs() * MAX_GAIN * 1.0001);
+        }
+    }
+
+    #[test]
+    fn attacks_faster_than_it_releases() {
+        let settings = AgcSettings::default();
+        let mut agc = Agc::new(settings, SAMPLE_RATE);
+        let gain_after = |agc: &mut Agc, level: f32, time: Duration| {
+            let samples = (time.as_secs_f32() * SAMPLE_RATE as f32) as usize;
+            (0..samples).map(|_| agc.process(level)).last().unwrap() / level
+        };
+        // from unity gain, a level 4 times the target needs a gain of 0.25
+        let attacked = gain_after(&mut agc, 0.4, 5 * settings.attack);
+        assert!((attacked - 0.25).abs() < 0.01, "{}", attacked);
+        // going back down to the target needs a gain of 1 again
+        let released = gain_after(&mut agc, 0.1, 5 * settings.attack);
+        assert!(released < 0.5, "{}", released);
+        let released = gain_after(&mut agc, 0.1, 5 * settings.release);
+        assert!((released - 1.0).abs() < 0.05, "{}", released);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_target() {
+        Agc::new(
+            AgcSettings {
+                target_rms: 0.0,
+                ..AgcSettings::default()
+            },
+            SAMPLE_RATE,
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{noise::NoiseSource, trombone::PinkTrombone};
+
+/// A pool of independent `PinkTrombone` voices playing MIDI notes,
+/// for chords and other polyphony.
+///
+/// Each note is voiced by its own synthesizer. When all voices are busy,
+/// `note_on` steals the least recently used one. Released voices keep
+/// ringing out until they are reused, and those that have gone silent
+/// are not synthesized.
+#[derive(Clone)]
+pub struct PinkTromboneChoir {
+    voices: Vec<Voice>,
+    scratch: Vec<f32>,
+    /// increases with every note event, to find the least recently used voice
+    clock: u64,
+}
+
+#[derive(Clone)]
+struct Voice {
+    trombone: PinkTrombone,
+    note: Option<u8>,
+    last_used: u64,
+}
+
+/// voices that have rung down below this are not synthesized
+const SILENCE_THRESHOLD: f32 = 1e-4;
+
+impl PinkTromboneChoir {
+    /// Creates `voices` synthesizers at `sample_rate`, drawing their noise
+    /// from `rng`, with their pitch drift seeded from `seed` onwards.
+    /// Panics if `voices` is 0.
+    pub fn new(
+        voices: usize,
+        sample_rate: u32,
+        rng: &mut dyn NoiseSource<f64>,
+        seed: u16,
+    ) -> PinkTromboneChoir {
+        if voices == 0 {
+            panic!("choir needs at least one voice");
+        }
+        let voices = (0..voices)
+            .map(|k| {
+                let mut trombone = PinkTrombone::new(sample_rate, rng, seed.wrapping_add(k as u16));
+                trombone.set_always_voice(false);
+                Voice {
+                    trombone,
+                    note: None,
+                    last_used: 0,
+                }
+            })
+            .collect();
+        PinkTromboneChoir {
+            voices,
+            scratch: Vec::new(),
+            clock: 0,
+        }
+    }
+
+    /// Number of voices in the pool.
+    pub fn len(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Always false, as the pool has at least one voice.
+    pub fn is_empty(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    /// The synthesizers of the pool, e.g. to change their articulation
+    /// or voice quality. `note_on` sets their pitch, tenseness and voicing.
+    pub fn voices_mut(&mut self) -> impl Iterator<Item = &mut PinkTrombone> {
+        self.voices.iter_mut().map(|voice| &mut voice.trombone)
+    }
+
+    /// Notes currently held, in the order of the voices playing them.
+    pub fn active_notes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.voices.iter().filter_map(|voice| voice.note)
+    }
+
+    /// Starts voicing `note` (see `PinkTrombone::note_on`) on a voice of its
+    /// own: the one already playing it, else the least recently used idle
+    /// voice, else the least recently used busy one.
+    /// Velocity 0 is treated as `note_off`.
+    pub fn note_on(&mut self, note: u8, velocity: u8) {
+        if velocity == 0 {
+            self.note_off(note);
+            return;
+        }
+        let index = match self
+            .voices
+            .iter()
+            .position(|voice| voice.note == Some(note))
+        {
+            Some(index) => index,
+            None => self.least_recently_used(),
+        };
+        self.clock += 1;
+        let voice = &mut self.voices[index];
+        voice.trombone.note_on(note, velocity);
+        voice.note = Some(note);
+        voice.last_used = self.clock;
+    }
+
+    /// Releases the voice playing `note`, if any.
+    pub fn note_off(&mut self, note: u8) {
+        self.clock += 1;
+        for voice in &mut self.voices {
+            if voice.note == Some(note) {
+                voice.trombone.note_off();
+                voice.note = None;
+                voice.last_used = self.clock;
+            }
+        }
+    }
+
+    /// Releases all voices.
+    pub fn all_notes_off(&mut self) {
+        let notes: Vec<u8> = self.active_notes().collect();
+        for note in notes {
+            self.note_off(note);
+        }
+    }
+
+    fn least_recently_used(&self) -> usize {
+        let idle = self.voices.iter().any(|voice| voice.note.is_none());
+        self.voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| !idle || voice.note.is_none())
+            .min_by_key(|(_, voice)| voice.last_used)
+            .map(|(index, _)| index)
+            .unwrap()
+    }
+
+    /// Synthesizes and mixes all sounding voices into `buf`.
+    /// The mix is divided by the number of voices, so that it stays in the
+    /// range of a single voice however many notes are held.
+    pub fn synthesize(&mut self, buf: &mut [f32]) {
+        for x in buf.iter_mut() {
+            *x = 0.0;
+        }
+        self.scratch.resize(buf.len(), 0.0);
+        let gain = 1.0 / self.voices.len() as f32;
+        for voice in &mut self.voices {
+            if voice.note.is_none() && voice.trombone.is_silent(SILENCE_THRESHOLD) {
+                continue;
+            }
+            voice.trombone.synthesize(&mut self.scratch);
+            for (out, &x) in buf.iter_mut().zip(&self.scratch) {
+                *out += x * gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::xorshift::XorShift128;
+
+    #[test]
+    fn steals_least_recently_used_voice() {
+        let mut random = XorShift128::new(1);
+        let mut choir = PinkTromboneChoir::new(2, 48000, &mut random, 1);
+        let mut buffer = vec![0.0; 4800];
+        choir.synthesize(&mut buffer);
+        assert!(buffer.iter().all(|&x| x == 0.0));
+
+        choir.note_on(60, 100);
+        choir.note_on(64, 100);
+        choir.note_on(67, 100);
+        assert_eq!(choir.active_notes().collect::<Vec<_>>(), vec![67, 64]);
+        choir.synthesize(&mut buffer);
+        assert!(buffer.iter().any(|&x| x.abs() > 0.01));
+
+        choir.note_off(64);
+        assert_eq!(choir.active_notes().collect::<Vec<_>>(), vec![67]);
+        // the released voice is reused before the held one is stolen
+        choir.note_on(72, 100);
+        assert_eq!(choir.active_notes().collect::<Vec<_>>(), vec![67, 72]);
+    }
+}
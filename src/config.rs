@@ -0,0 +1,34 @@
+use std::f64::consts::PI;
+
+/// Tract shape settings that can be saved with `PinkTrombone::config`
+/// and morphed to with `PinkTrombone::morph_to`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinkTromboneConfig {
+    pub tongue_index: f64,
+    pub tongue_diameter: f64,
+    pub velum_open: bool,
+    /// Cell areas from the glottis to the lips used instead of the tongue
+    /// position, see `PinkTrombone::set_area_function`.
+    pub area_function: Option<Vec<f64>>,
+}
+
+/// How `PinkTrombone::morph_to` moves between tract shapes over time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MorphCurve {
+    #[default]
+    /// Constant speed from start to end.
+    Linear,
+    /// Half a cosine period: starts and ends slowly, with no sudden
+    /// change of speed at either end.
+    Cosine,
+}
+
+impl MorphCurve {
+    /// Progress of the morph, 0..1, at `t` of its duration (0..1).
+    pub fn weight(self, t: f64) -> f64 {
+        match self {
+            MorphCurve::Linear => t,
+            MorphCurve::Cosine => 0.5 - 0.5 * (PI * t).cos(),
+        }
+    }
+}
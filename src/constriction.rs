@@ -0,0 +1,54 @@
+use std::f64::consts::PI;
+
+use crate::tract::Tract;
+
+/// A narrowing of the vocal tract around a position,
+/// like the one made by touching the tract in the original Pink Trombone.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Constriction {
+    /// Position along the tract, in cells (see `landmarks`).
+    pub index: f64,
+    /// Diameter at the narrowest point. 0 closes the tract completely.
+    pub diameter: f64,
+}
+
+impl Constriction {
+    /// Narrows `target_diameter` around `index`, smoothly blending back to
+    /// the original diameters over a width that depends on the position.
+    pub fn apply(&self, target_diameter: &mut [f64; Tract::N]) {
+        let index = self.index;
+        let diameter = self.diameter.max(0.0);
+        let width = if index < 25.0 {
+            10.0
+        } else if index >= Tract::TIP_START as f64 {
+            5.0
+        } else {
+            10.0 - 5.0 * (index - 25.0) / (Tract::TIP_START as f64 - 25.0)
+        };
+        if index < 2.0 || index >= Tract::N as f64 {
+            return;
+        }
+
+        let int_index = index.round() as i64;
+        let start = -(width.ceil() as i64) - 1;
+        let end = (width + 1.0).ceil() as i64;
+        for offset in start..end {
+            let i = int_index + offset;
+            if i < 0 || i >= Tract::N as i64 {
+                continue;
+            }
+            let i = i as usize;
+            let relative_position = (i as f64 - index).abs() - 0.5;
+            let shrink = if relative_position <= 0.0 {
+                0.0
+            } else if relative_position > width {
+                1.0
+            } else {
+                0.5 * (1.0 - (PI * relative_position / width).cos())
+            };
+            if diameter < target_diameter[i] {
+                target_diameter[i] = diameter + (target_diameter[i] - diameter) * shrink;
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::f64::consts::PI;
+
+use crate::noise::Filter;
+
+/// frequencies below this are attenuated by more than 3 dB, in Hz
+const CUTOFF: f64 = 40.0;
+
+/// One-pole DC blocking filter, `y[n] = x[n] - x[n-1] + r y[n-1]`.
+/// The pole `r` is placed for a -3 dB point at `CUTOFF`,
+/// about 0.995 at 48kHz.
+#[derive(Clone)]
+pub struct DcBlocker {
+    r: f64,
+    last_input: f64,
+    last_output: f64,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: u32) -> DcBlocker {
+        DcBlocker {
+            r: (1.0 - 2.0 * PI * CUTOFF / sample_rate as f64).max(0.0),
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+}
+
+impl Filter for DcBlocker {
+    fn filter(&mut self, x: f64) -> f64 {
+        let y = x - self.last_input + self.r * self.last_output;
+        self.last_input = x;
+        self.last_output = y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn removes_offset_and_passes_tones() {
+        let mut blocker = DcBlocker::new(SAMPLE_RATE);
+        let mut peak: f64 = 0.0;
+        let mut last = 0.0;
+        for n in 0..SAMPLE_RATE as usize {
+            let tone = (2.0 * PI * 440.0 * n as f64 / SAMPLE_RATE as f64).sin();
+            last = blocker.filter(0.5 + tone);
+            if n > SAMPLE_RATE as usize / 2 {
+                peak = peak.max(last.abs());
+            }
+        }
+        assert!(last.abs() < 1.0);
+        assert!((peak - 1.0).abs() < 0.01, "{}", peak);
+
+        let mut blocker = DcBlocker::new(SAMPLE_RATE);
+        let settled = (0..SAMPLE_RATE)
+            .map(|_| blocker.filter(0.5))
+            .last()
+            .unwrap();
+        assert!(settled.abs() < 1e-6, "{}", settled);
+    }
+}
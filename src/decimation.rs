@@ -0,0 +1,160 @@
+use std::f64::consts::PI;
+
+/// How tract samples (computed at twice the output rate) are reduced
+/// to output samples.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DecimationMode {
+    #[default]
+    /// Average each pair of tract samples. Cheap, but lets through
+    /// much of the content above the output Nyquist frequency.
+    Average,
+    /// Polyphase half-band FIR low-pass filter.
+    /// Strongly attenuates content above the output Nyquist frequency
+    /// at the cost of 7.5 output samples of latency.
+    HalfBand,
+}
+
+#[derive(Clone, Default)]
+pub enum Decimator {
+    #[default]
+    Average,
+    HalfBand(Box<HalfBandFilter>),
+}
+
+impl Decimator {
+    pub fn new(mode: DecimationMode) -> Decimator {
+        match mode {
+            DecimationMode::Average => Decimator::Average,
+            DecimationMode::HalfBand => Decimator::HalfBand(Box::new(HalfBandFilter::new())),
+        }
+    }
+
+    pub fn mode(&self) -> DecimationMode {
+        match self {
+            Decimator::Average => DecimationMode::Average,
+            Decimator::HalfBand(_) => DecimationMode::HalfBand,
+        }
+    }
+
+    /// Takes two consecutive input samples and produces one output sample.
+    pub fn decimate(&mut self, x0: f32, x1: f32) -> f32 {
+        match self {
+            Decimator::Average => (x0 + x1) * 0.5,
+            Decimator::HalfBand(filter) => filter.decimate(x0, x1),
+        }
+    }
+}
+
+const TAPS: usize = 31;
+const CENTER: usize = TAPS / 2;
+
+#[derive(Clone)]
+pub struct HalfBandFilter {
+    /// even-indexed taps, the only non-zero ones besides the center
+    even_taps: [f64; CENTER + 1],
+    center_tap: f64,
+    history: [f64; TAPS],
+    pos: usize,
+}
+
+impl HalfBandFilter {
+    pub fn new() -> HalfBandFilter {
+        // Blackman-windowed sinc with the cutoff at a quarter of the input rate
+        let mut taps = [0.0; TAPS];
+        for (n, tap) in taps.iter_mut().enumerate() {
+            let m = n as f64 - CENTER as f64;
+            let sinc = if m == 0.0 {
+                1.0
+            } else {
+                (PI * m / 2.0).sin() / (PI * m / 2.0)
+            };
+            let phase = 2.0 * PI * n as f64 / (TAPS - 1) as f64;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            *tap = sinc * window;
+        }
+        let sum: f64 = taps.iter().sum();
+
+        let mut even_taps = [0.0; CENTER + 1];
+        for (k, tap) in even_taps.iter_mut().enumerate() {
+            *tap = taps[2 * k] / sum;
+        }
+
+        HalfBandFilter {
+            even_taps,
+            center_tap: taps[CENTER] / sum,
+            history: [0.0; TAPS],
+            pos: 0,
+        }
+    }
+
+    fn push(&mut self, x: f32) {
+        self.pos = (self.pos + 1) % TAPS;
+        self.history[self.pos] = x as f64;
+    }
+
+    /// `k` samples ago
+    fn past(&self, k: usize) -> f64 {
+        self.history[(self.pos + TAPS - k) % TAPS]
+    }
+
+    pub fn decimate(&mut self, x0: f32, x1: f32) -> f32 {
+        self.push(x0);
+        self.push(x1);
+        let mut y = self.center_tap * self.past(CENTER);
+        for (k, tap) in self.even_taps.iter().enumerate() {
+            y += tap * self.past(2 * k);
+        }
+        y as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Amplitude of the `freq` component of `signal`, `freq` relative to the sample rate.
+    fn magnitude_at(signal: &[f32], freq: f64) -> f64 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (n, &x) in signal.iter().enumerate() {
+            let phase = 2.0 * PI * freq * n as f64;
+            re += x as f64 * phase.cos();
+            im -= x as f64 * phase.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+    }
+
+    fn decimate_tone(mode: DecimationMode, freq: f64) -> Vec<f32> {
+        let mut decimator = Decimator::new(mode);
+        let input: Vec<f32> = (0..8192)
+            .map(|n| (2.0 * PI * freq * n as f64).sin() as f32)
+            .collect();
+        let output: Vec<f32> = input
+            .chunks(2)
+            .map(|pair| decimator.decimate(pair[0], pair[1]))
+            .collect();
+        // skip the filter warm-up
+        output[TAPS..].to_vec()
+    }
+
+    #[test]
+    fn half_band_reduces_aliasing() {
+        // 0.375 of the input rate is above the output Nyquist frequency,
+        // and aliases to 0.25 of the output rate
+        let input_freq = 0.375;
+        let alias_freq = 1.0 - 2.0 * input_freq;
+        let average = decimate_tone(DecimationMode::Average, input_freq);
+        let half_band = decimate_tone(DecimationMode::HalfBand, input_freq);
+        let average_alias = magnitude_at(&average, alias_freq);
+        let half_band_alias = magnitude_at(&half_band, alias_freq);
+        assert!(average_alias > 0.3);
+        assert!(half_band_alias < average_alias * 0.01);
+    }
+
+    #[test]
+    fn half_band_passes_low_frequencies() {
+        let input_freq = 0.05;
+        let half_band = decimate_tone(DecimationMode::HalfBand, input_freq);
+        let passed = magnitude_at(&half_band, 2.0 * input_freq);
+        assert!((passed - 1.0).abs() < 0.01);
+    }
+}
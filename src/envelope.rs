@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+/// One-pole smoothing of the rectified signal.
+#[derive(Clone)]
+pub struct EnvelopeFollower {
+    smoothing: Duration,
+    coefficient: f32,
+    level: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(smoothing: Duration, sample_rate: u32) -> EnvelopeFollower {
+        let samples = smoothing.as_secs_f32() * sample_rate as f32;
+        EnvelopeFollower {
+            smoothing,
+            coefficient: if samples > 0.0 {
+                1.0 - (-1.0 / samples).exp()
+            } else {
+                1.0
+            },
+            level: 0.0,
+        }
+    }
+
+    pub fn smoothing(&self) -> Duration {
+        self.smoothing
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.level += self.coefficient * (x.abs() - self.level);
+        self.level
+    }
+}
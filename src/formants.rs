@@ -0,0 +1,183 @@
+use std::f64::consts::PI;
+
+/// formants are searched for up to this frequency, in Hz
+const MAX_FREQUENCY: f64 = 5000.0;
+/// spacing of the spectrum the peaks are picked from, in Hz
+const RESOLUTION: f64 = 20.0;
+/// peaks below this are the glottal end rather than formants
+const MIN_FREQUENCY: f64 = 90.0;
+
+/// Frequencies of the spectral peaks of `response`, in ascending order.
+pub fn estimate(response: &[f32], sample_rate: u32) -> Vec<f32> {
+    let max_frequency = MAX_FREQUENCY.min(sample_rate as f64 / 2.0);
+    let bins = (max_frequency / RESOLUTION) as usize;
+    let magnitude: Vec<f64> = (0..=bins)
+        .map(|bin| {
+            let w = 2.0 * PI * bin as f64 * RESOLUTION / sample_rate as f64;
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, &x) in response.iter().enumerate() {
+                re += x as f64 * (w * n as f64).cos();
+                im -= x as f64 * (w * n as f64).sin();
+            }
+            (re * re + im * im).sqrt().max(1e-12).ln()
+        })
+        .collect();
+
+    let mut peaks = Vec::new();
+    for bin in 1..bins {
+        let (left, center, right) = (magnitude[bin - 1], magnitude[bin], magnitude[bin + 1]);
+        if center > left && center >= right {
+            // parabolic interpolation between bins
+            let offset = 0.5 * (left - right) / (left - 2.0 * center + right);
+            let frequency = (bin as f64 + offset) * RESOLUTION;
+            if frequency >= MIN_FREQUENCY {
+                peaks.push(frequency as f32);
+            }
+        }
+    }
+    peaks
+}
+
+/// Magnitude in dB at `frequency` Hz of the transfer function from the
+/// glottis to the lips of a lossless Kelly-Lochbaum tube: cells of one
+/// sample delay at `sample_rate` each way, scattering at the junctions with
+/// `reflection` (index 0 unused), and ends reflecting with
+/// `glottal_reflection` and `lip_reflection`.
+pub fn transfer_db(
+    reflection: &[f64],
+    glottal_reflection: f64,
+    lip_reflection: f64,
+    sample_rate: u32,
+    frequency: f64,
+) -> f64 {
+    let w = 2.0 * PI * frequency / sample_rate as f64;
+    let delay = Complex::new(w.cos(), -w.sin());
+    let n = reflection.len();
+    // ratio of the left- to the right-going wave in each cell,
+    // from the lips backwards
+    let mut ratio = delay.scale(lip_reflection);
+    // gain of the right-going wave from the first cell to the last
+    let mut gain = Complex::new(1.0, 0.0);
+    for &r in reflection[1..n].iter().rev() {
+        let transmission = delay
+            .scale(1.0 - r)
+            .div(Complex::new(1.0, 0.0).add(delay.mul(ratio).scale(r)));
+        gain = gain.mul(transmission);
+        ratio = delay.mul(
+            ratio
+                .mul(transmission)
+                .scale(1.0 + r)
+                .add(Complex::new(r, 0.0)),
+        );
+    }
+    let glottis = delay.div(Complex::new(1.0, 0.0).sub(delay.mul(ratio).scale(glottal_reflection)));
+    let magnitude = glottis.mul(gain).abs();
+    20.0 * magnitude.max(1e-12).log10()
+}
+
+#[derive(Copy, Clone)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+
+    fn div(self, other: Complex) -> Complex {
+        let norm = other.re * other.re + other.im * other.im;
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / norm,
+            (self.im * other.re - self.re * other.im) / norm,
+        )
+    }
+
+    fn scale(self, factor: f64) -> Complex {
+        Complex::new(self.re * factor, self.im * factor)
+    }
+
+    fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+/// Minimizes `f` over the unit square with the Nelder-Mead method,
+/// starting around `start`. Returns the best point found.
+pub fn minimize<F: FnMut([f64; 2]) -> f64>(
+    mut f: F,
+    start: [f64; 2],
+    iterations: usize,
+) -> [f64; 2] {
+    const STEP: f64 = 0.2;
+    let clamp = |p: [f64; 2]| [p[0].clamp(0.0, 1.0), p[1].clamp(0.0, 1.0)];
+    let towards =
+        |a: [f64; 2], b: [f64; 2], t: f64| [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+    // Points outside of the square are evaluated at the nearest point
+    // inside, plus a penalty. Clamping the points themselves instead would
+    // collapse the simplex onto the edges.
+    let mut f = |p: [f64; 2]| {
+        let inside = clamp(p);
+        f(inside) + (p[0] - inside[0]).powi(2) + (p[1] - inside[1]).powi(2)
+    };
+
+    let start = clamp(start);
+    // step into the square from the edges
+    let dx = if start[0] + STEP <= 1.0 { STEP } else { -STEP };
+    let dy = if start[1] + STEP <= 1.0 { STEP } else { -STEP };
+    let mut simplex: Vec<([f64; 2], f64)> =
+        [start, [start[0] + dx, start[1]], [start[0], start[1] + dy]]
+            .iter()
+            .map(|&p| (p, f(p)))
+            .collect();
+
+    for _ in 0..iterations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, worst) = (simplex[0], simplex[2]);
+        let centroid = towards(simplex[0].0, simplex[1].0, 0.5);
+
+        let reflected = towards(worst.0, centroid, 2.0);
+        let reflected_value = f(reflected);
+        if reflected_value < best.1 {
+            let expanded = towards(worst.0, centroid, 3.0);
+            let expanded_value = f(expanded);
+            simplex[2] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[1].1 {
+            simplex[2] = (reflected, reflected_value);
+        } else {
+            let contracted = towards(worst.0, centroid, 0.5);
+            let contracted_value = f(contracted);
+            if contracted_value < worst.1 {
+                simplex[2] = (contracted, contracted_value);
+            } else {
+                // shrink towards the best point
+                for vertex in simplex.iter_mut().skip(1) {
+                    let p = towards(best.0, vertex.0, 0.5);
+                    *vertex = (p, f(p));
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    clamp(simplex[0].0)
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use crate::phoneme::Phoneme;
+
+/// One step of an utterance rendered by `PinkTrombone::say`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Gesture {
+    /// A voiced vowel, glided to from the previous tongue position.
+    Vowel(Phoneme, Duration),
+    /// A fricative: a narrow constriction at `position` (a cell index,
+    /// see `landmarks`) with turbulence noise, e.g. 36 and 0.4 for "s".
+    /// Voiced fricatives ("z", "v") keep the glottis sounding.
+    Fricative {
+        position: f64,
+        diameter: f64,
+        duration: Duration,
+        voiced: bool,
+    },
+    /// A plosive closing the tract at `position` for `closure`,
+    /// then opening it over `release` (see `PinkTrombone::plosive`).
+    /// Unvoiced plosives ("p", "t", "k") stop voicing for the closure.
+    Plosive {
+        position: usize,
+        closure: Duration,
+        release: Duration,
+        voiced: bool,
+    },
+    /// No voicing. The tract keeps its shape.
+    Silence(Duration),
+}
+
+impl Gesture {
+    /// Time the gesture takes in `PinkTrombone::say`.
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Gesture::Vowel(_, duration) => duration,
+            Gesture::Fricative { duration, .. } => duration,
+            Gesture::Plosive {
+                closure, release, ..
+            } => closure + release,
+            Gesture::Silence(duration) => duration,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::f32::consts::PI;
+
+/// Voicing parameters of the current glottal cycle.
+/// They only change at the start of a cycle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SourceParams {
+    /// 0 for a breathy voice, 1 for a pressed one.
+    /// Drift and the voicing attack can take it outside of 0..1.
+    pub tenseness: f32,
+    /// Frequency of the cycle in Hz.
+    pub frequency: f32,
+}
+
+/// Shape of the glottal flow derivative over one glottal cycle.
+///
+/// `Glottis` handles timing, intensity and loudness, and asks the source
+/// for the waveform at each `phase` (0..1) of the cycle. The waveform
+/// should peak at about ±1 and stay finite for any parameters.
+/// Implement `Clone` to get `box_clone` for free.
+pub trait GlottalSource: GlottalSourceClone + Send + Sync {
+    fn sample(&mut self, phase: f32, params: &SourceParams) -> f32;
+}
+
+/// Cloning of boxed glottal sources, implemented for all `Clone` ones.
+pub trait GlottalSourceClone {
+    fn box_clone(&self) -> Box<dyn GlottalSource>;
+}
+
+impl<T: GlottalSource + Clone + 'static> GlottalSourceClone for T {
+    fn box_clone(&self) -> Box<dyn GlottalSource> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn GlottalSource> {
+    fn clone(&self) -> Box<dyn GlottalSource> {
+        self.box_clone()
+    }
+}
+
+/// The default source: a Liljencrants-Fant waveform, with the R<sub>d</sub>
+/// shape parameter derived from tenseness.
+#[derive(Clone)]
+pub struct LfSource {
+    /// tenseness the waveform state was set up for
+    tenseness: f32,
+    alpha: f32,
+    e0: f32,
+    epsilon: f32,
+    shift: f32,
+    delta: f32,
+    te: f32,
+    omega: f32,
+}
+
+impl LfSource {
+    pub fn new() -> LfSource {
+        LfSource {
+            tenseness: f32::NAN,
+            alpha: 0.0,
+            e0: 0.0,
+            epsilon: 0.0,
+            shift: 0.0,
+            delta: 0.0,
+            te: 0.0,
+            omega: 0.0,
+        }
+    }
+
+    fn setup_waveform(&mut self, tenseness: f32) {
+        self.tenseness = tenseness;
+        let rd = (3.0 * (1.0 - tenseness)).clamp(0.5, 2.7);
+
+        // normalized to time = 1, Ee = 1
+        let ra = -0.01 + 0.048 * rd;
+
+        let rk = 0.224 + 0.118 * rd;
+        let rg = (rk / 4.0) * (0.5 + 1.2 * rk) / (0.11 * rd - ra * (0.5 + 1.2 * rk));
+
+        let ta = ra;
+        let tp = 1.0 / (2.0 * rg);
+        let te = tp + tp * rk;
+
+        let epsilon = 1.0 / ta;
+        let shift = (-epsilon * (1.0 - te)).exp();
+        let delta = 1.0 - shift; // divide by self to scale RHS
+
+        let rhs_integral = ((1.0 / epsilon) * (shift - 1.0) + (1.0 - te) * shift) / delta;
+        let total_lower_integral = rhs_integral - (te - tp) / 2.0;
+        let total_upper_integral = -total_lower_integral;
+
+        let omega = PI / tp;
+        let s = (omega * te).sin();
+
+        // need E0*e^(alpha*Te)*s = -1 (to meet the return at -1)
+        // and E0*e^(alpha*Tp/2) * Tp*2/pi = totalUpperIntegral
+        //             (our approximation of the integral up to Tp)
+        // writing x for e^alpha,
+        // have E0*x^Te*s = -1 and E0 * x^(Tp/2) * Tp*2/pi = totalUpperIntegral
+        // dividing the second by the first,
+        // letting y = x^(Tp/2 - Te),
+        // y * Tp*2 / (pi*s) = -totalUpperIntegral;
+
+        // y must stay positive for ln, or the waveform becomes NaN
+        let y = (-PI * s * total_upper_integral / (tp * 2.0)).max(f32::MIN_POSITIVE);
+        let z = y.ln();
+        let alpha = z / (tp / 2.0 - te);
+        let e0 = -1.0 / (s * (alpha * te).exp());
+
+        // keep the previous waveform rather than poisoning the output forever
+        let coefficients = [alpha, e0, epsilon, shift, delta, te, omega];
+        if coefficients.iter().any(|c| !c.is_finite()) || delta == 0.0 {
+            return;
+        }
+
+        self.alpha = alpha;
+        self.e0 = e0;
+        self.epsilon = epsilon;
+        self.shift = shift;
+        self.delta = delta;
+        self.te = te;
+        self.omega = omega;
+    }
+}
+
+impl Default for LfSource {
+    fn default() -> LfSource {
+        LfSource::new()
+    }
+}
+
+impl GlottalSource for LfSource {
+    fn sample(&mut self, phase: f32, params: &SourceParams) -> f32 {
+        if params.tenseness.to_bits() != self.tenseness.to_bits() {
+            self.setup_waveform(params.tenseness);
+        }
+        let t = phase;
+        if t > self.te {
+            (-(-self.epsilon * (t - self.te)).exp() + self.shift) / self.delta
+        } else {
+            self.e0 * (self.alpha * t).exp() * (self.omega * t).sin()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waveform_finite_for_any_tenseness() {
+        let mut source = LfSource::new();
+        // includes values outside of 0..1 that drift and attack can produce
+        for i in -1000..=4000 {
+            let params = SourceParams {
+                tenseness: i as f32 / 1000.0,
+                frequency: 140.0,
+            };
+            for k in 0..=100 {
+                let t = k as f32 / 100.0;
+                assert!(
+                    source.sample(t, &params).is_finite(),
+                    "{}",
+                    params.tenseness
+                );
+            }
+        }
+    }
+}
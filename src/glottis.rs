@@ -1,55 +1,156 @@
 use std::f32::consts::PI;
 
 use crate::{
-    math::interpolate,
-    noise::{self, NoiseSource},
+    glottal_source::{GlottalSource, LfSource, SourceParams},
+    math::{self, interpolate},
+    noise::{self, FilteredNoise, NoiseMode, NoiseSource},
     noise_gen::NoiseGenerator,
+    ramp::Ramp,
 };
 
+/// Glottal source: a voice waveform (Liljencrants-Fant by default,
+/// see `set_source`) with aspiration noise.
+///
+/// Runs at the output sample rate. Call `adjust_parameters` once per block,
+/// then `step` once per sample.
+#[derive(Clone)]
 pub struct Glottis {
     pub always_voice: bool,
     pub auto_wobble: bool,
-    is_touched: bool,
-    pub target_tenseness: f32,
-    pub target_frequency: f32,
-    pub vibrato_amount: f32,
-    pub vibrato_frequency: f32,
+    /// when false, vibrato and all pitch drift are disabled
+    pub vibrato_enabled: bool,
+    /// when false, tenseness does not wander randomly around its target
+    pub tenseness_drift: bool,
+    /// whether voicing was requested, e.g. by a note being held
+    pub is_touched: bool,
+    target_tenseness: f32,
+    target_frequency: f32,
+    vibrato_amount: f32,
+    vibrato_frequency: f32,
+    /// upper limit of the frequency, including vibrato, in Hz
+    max_frequency: f32,
+    /// when true, intensity, frequency and tenseness jump to their targets
+    /// with no gliding, vibrato, drift or attack
+    pub frozen: bool,
+    /// scales how strongly aspiration noise is pulsed by the glottal cycle
+    /// and wobbles over time, 1 by default
+    aspiration_modulation: f32,
+    /// when true, the pulses of the first `ONSET_CYCLES` glottal cycles
+    /// after `is_touched` becomes true grow progressively
+    pub natural_onset: bool,
 
     noise_generator: NoiseGenerator,
 
     sample_rate: u32,
-    sample_count: u64,
-    pub intensity: f32,
-    pub loudness: f32,
-    smooth_frequency: f32,
-    time_in_waveform: f32,
+    pub(crate) sample_count: u64,
+    intensity: f32,
+    /// amplitude of the glottal pulses, updated at the start
+    /// of every cycle according to `loudness_mode`
+    loudness: f32,
+    loudness_mode: LoudnessMode,
+    pub(crate) smooth_frequency: f32,
+    pub(crate) time_in_waveform: f32,
     old_tenseness: f32,
-    new_tenseness: f32,
+    pub(crate) new_tenseness: f32,
     old_frequency: f32,
-    new_frequency: f32,
-    aspiration_noise_source: Box<dyn FnMut() -> f64 + Send + 'static>,
-    waveform_length: f32,
-
-    // waveform state
-    alpha: f32,
-    e0: f32,
-    epsilon: f32,
-    shift: f32,
-    delta: f32,
-    te: f32,
-    omega: f32,
+    pub(crate) new_frequency: f32,
+    /// `None` for a glottis without aspiration, see `without_aspiration`
+    aspiration_noise_source: Option<FilteredNoise>,
+    pub(crate) waveform_length: f32,
+    /// whether the last step started a new glottal cycle
+    pub(crate) cycle_started: bool,
+    /// `sample_count` at which the current pre-aspiration ends
+    pre_aspiration_end: u64,
+    /// `is_touched` as of the last block, to detect note-ons
+    was_touched: bool,
+    /// glottal cycles since the last natural onset started,
+    /// `ONSET_CYCLES` when there is none in progress
+    onset_cycle: u32,
+    /// per-sample ramps of `target_frequency`, `target_tenseness` and
+    /// `intensity`, kept until the block after they finish
+    pub(crate) frequency_ramp: Option<Ramp>,
+    pub(crate) tenseness_ramp: Option<Ramp>,
+    pub(crate) intensity_ramp: Option<Ramp>,
+    /// relative pitch change by vibrato at the start and end of the block
+    old_vibrato: f32,
+    new_vibrato: f32,
+    /// drift and attack added to `target_tenseness` in the current block
+    tenseness_offset: f32,
+
+    source: Box<dyn GlottalSource>,
+    /// parameters of the current glottal cycle
+    source_params: SourceParams,
 }
 
+/// How the amplitude of the glottal pulses (`Glottis::loudness`)
+/// is chosen at the start of every glottal cycle.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LoudnessMode {
+    /// Derived from tenseness, so that a breathy voice is quieter.
+    #[default]
+    FollowTenseness,
+    /// Always the specified value, 0..1.
+    Fixed(f32),
+    /// Left as it is, so that it only changes when set directly.
+    Manual,
+}
+
+/// Rates below were originally specified per 512-sample block, whatever
+/// the sample rate. They are now per second, matching the old timing only
+/// at 48kHz: at 44.1kHz, for example, voicing starts and stops about 9%
+/// faster than it used to.
+const REFERENCE_BLOCKS_PER_SECOND: f32 = 48000.0 / 512.0;
+/// intensity change per second while voicing
+const INTENSITY_ATTACK_RATE: f32 = 0.13 * REFERENCE_BLOCKS_PER_SECOND;
+/// intensity change per second while not voicing
+const INTENSITY_RELEASE_RATE: f32 = 0.05 * REFERENCE_BLOCKS_PER_SECOND;
+/// relative frequency change per second towards the target
+const FREQUENCY_GLIDE_RATE: f32 = 0.1 * REFERENCE_BLOCKS_PER_SECOND;
+/// number of glottal cycles a natural onset builds up over
+const ONSET_CYCLES: u32 = 4;
+/// lower limit of the frequency, including vibrato, in Hz
+pub(crate) const MIN_FREQUENCY: f32 = 10.0;
+
 impl Glottis {
     pub fn new(sample_rate: u32, rng: &mut dyn NoiseSource<f64>, seed: u16) -> Glottis {
+        Glottis::with_noise_mode(sample_rate, rng, seed, NoiseMode::default())
+    }
+
+    pub fn with_noise_mode(
+        sample_rate: u32,
+        rng: &mut dyn NoiseSource<f64>,
+        seed: u16,
+        mode: NoiseMode,
+    ) -> Glottis {
+        let aspiration = Glottis::new_aspiration_noise_source(sample_rate, mode, rng);
+        Glottis::with_aspiration(sample_rate, Some(aspiration), seed)
+    }
+
+    /// A glottis producing only the glottal pulses, with no aspiration
+    /// noise until `set_noise_mode` is called.
+    pub fn without_aspiration(sample_rate: u32, seed: u16) -> Glottis {
+        Glottis::with_aspiration(sample_rate, None, seed)
+    }
+
+    fn with_aspiration(
+        sample_rate: u32,
+        aspiration_noise_source: Option<FilteredNoise>,
+        seed: u16,
+    ) -> Glottis {
         let mut glottis = Glottis {
             always_voice: true,
             auto_wobble: true,
+            vibrato_enabled: true,
+            tenseness_drift: true,
             is_touched: false,
             target_tenseness: 0.6,
             target_frequency: 140.0,
             vibrato_amount: 0.005,
             vibrato_frequency: 6.0,
+            max_frequency: sample_rate as f32 / 4.0,
+            frozen: false,
+            aspiration_modulation: 1.0,
+            natural_onset: false,
 
             noise_generator: NoiseGenerator::new(seed),
 
@@ -58,6 +159,7 @@ impl Glottis {
             sample_count: 0,
             intensity: 0.0,
             loudness: 1.0,
+            loudness_mode: LoudnessMode::default(),
             smooth_frequency: 140.0,
             time_in_waveform: 0.0,
             old_tenseness: 0.6,
@@ -65,24 +167,25 @@ impl Glottis {
             old_frequency: 140.0,
             new_frequency: 140.0,
 
-            aspiration_noise_source: noise::new_filtered_noise_source(
-                500.0,
-                0.5,
-                sample_rate,
-                0x8000,
-                rng,
-            ),
+            aspiration_noise_source,
 
             waveform_length: 0.0,
-
-            // waveform state
-            alpha: 0.0,
-            e0: 0.0,
-            epsilon: 0.0,
-            shift: 0.0,
-            delta: 0.0,
-            te: 0.0,
-            omega: 0.0,
+            cycle_started: false,
+            pre_aspiration_end: 0,
+            was_touched: false,
+            onset_cycle: ONSET_CYCLES,
+            frequency_ramp: None,
+            tenseness_ramp: None,
+            intensity_ramp: None,
+            old_vibrato: 0.0,
+            new_vibrato: 0.0,
+            tenseness_offset: 0.0,
+
+            source: Box::new(LfSource::new()),
+            source_params: SourceParams {
+                tenseness: 0.6,
+                frequency: 140.0,
+            },
         };
 
         glottis.setup_waveform(0.0);
@@ -90,158 +193,536 @@ impl Glottis {
         glottis
     }
 
+    fn new_aspiration_noise_source(
+        sample_rate: u32,
+        mode: NoiseMode,
+        rng: &mut dyn NoiseSource<f64>,
+    ) -> FilteredNoise {
+        noise::new_filtered_noise_source(500.0, 0.5, sample_rate, mode, rng)
+    }
+
+    /// Replaces the aspiration noise with a new one generated according to `mode`.
+    pub fn set_noise_mode(&mut self, mode: NoiseMode, rng: &mut dyn NoiseSource<f64>) {
+        self.aspiration_noise_source = Some(Glottis::new_aspiration_noise_source(
+            self.sample_rate,
+            mode,
+            rng,
+        ));
+    }
+
+    /// Reseeds the pitch and tenseness drift with `seed`
+    /// and regenerates the aspiration noise from `rng`.
+    pub fn reseed(&mut self, seed: u16, rng: &mut dyn NoiseSource<f64>) {
+        self.noise_generator.set_seed(seed);
+        if let Some(noise) = &mut self.aspiration_noise_source {
+            noise.reseed(rng);
+        }
+    }
+
+    /// Overwrites the aspiration noise with new values from `rng` in place,
+    /// continuing from the same filter state and loop position.
+    pub fn refill_noise(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        if let Some(noise) = &mut self.aspiration_noise_source {
+            noise.refill(rng);
+        }
+    }
+
+    /// Tenseness used for the current block.
+    pub fn current_tenseness(&self) -> f32 {
+        self.new_tenseness
+    }
+
+    /// Frequency in Hz used for the current block.
+    pub fn current_frequency(&self) -> f32 {
+        self.new_frequency
+    }
+
+    /// Target fundamental frequency in Hz, 10.. (see `MIN_FREQUENCY`).
+    /// Setting it cancels a frequency ramp, values outside of the range
+    /// are clamped to it, and NaN is ignored.
+    pub fn target_frequency(&self) -> f32 {
+        self.target_frequency
+    }
+    pub fn set_target_frequency(&mut self, target_frequency: f32) {
+        if !target_frequency.is_nan() {
+            self.frequency_ramp = None;
+        }
+        self.target_frequency = math::clamp_or(
+            target_frequency,
+            MIN_FREQUENCY,
+            f32::MAX,
+            self.target_frequency,
+        )
+    }
+
+    /// Upper limit of the frequency, including vibrato, in Hz, at least
+    /// `MIN_FREQUENCY`. NaN is ignored.
+    pub fn max_frequency(&self) -> f32 {
+        self.max_frequency
+    }
+    pub fn set_max_frequency(&mut self, max_frequency: f32) {
+        self.max_frequency =
+            math::clamp_or(max_frequency, MIN_FREQUENCY, f32::MAX, self.max_frequency)
+    }
+
+    /// Target tenseness, 0..1. Setting it cancels a tenseness ramp,
+    /// values outside of the range are clamped to it, and NaN is ignored.
+    pub fn target_tenseness(&self) -> f32 {
+        self.target_tenseness
+    }
+    pub fn set_target_tenseness(&mut self, target_tenseness: f32) {
+        if !target_tenseness.is_nan() {
+            self.tenseness_ramp = None;
+        }
+        self.target_tenseness = math::clamp_or(target_tenseness, 0.0, 1.0, self.target_tenseness)
+    }
+
+    /// Voicing intensity, 0..1. Setting it cancels an intensity ramp,
+    /// values outside of the range are clamped to it, and NaN is ignored.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+    pub fn set_intensity(&mut self, intensity: f32) {
+        if !intensity.is_nan() {
+            self.intensity_ramp = None;
+        }
+        self.intensity = math::clamp_or(intensity, 0.0, 1.0, self.intensity)
+    }
+
+    /// Amplitude of the glottal pulses, 0..1. Setting it switches to
+    /// `LoudnessMode::Manual`; values outside of the range are clamped
+    /// to it, and NaN is ignored.
+    pub fn loudness(&self) -> f32 {
+        self.loudness
+    }
+    pub fn set_loudness(&mut self, loudness: f32) {
+        if loudness.is_nan() {
+            return;
+        }
+        self.loudness_mode = LoudnessMode::Manual;
+        self.loudness = loudness.clamp(0.0, 1.0);
+    }
+
+    /// How `loudness` is chosen every glottal cycle. Switching to `Fixed`
+    /// applies the value, clamped to 0..1, immediately; NaN is ignored.
+    pub fn loudness_mode(&self) -> LoudnessMode {
+        self.loudness_mode
+    }
+    pub fn set_loudness_mode(&mut self, mode: LoudnessMode) {
+        self.loudness_mode = match mode {
+            LoudnessMode::Fixed(loudness) if loudness.is_nan() => return,
+            LoudnessMode::Fixed(loudness) => {
+                let loudness = loudness.clamp(0.0, 1.0);
+                self.loudness = loudness;
+                LoudnessMode::Fixed(loudness)
+            }
+            mode => mode,
+        };
+    }
+
+    /// Depth of the vibrato, 0.. (negative values are clamped to 0).
+    /// NaN is ignored.
+    pub fn vibrato_amount(&self) -> f32 {
+        self.vibrato_amount
+    }
+    pub fn set_vibrato_amount(&mut self, vibrato_amount: f32) {
+        self.vibrato_amount = math::clamp_or(vibrato_amount, 0.0, f32::MAX, self.vibrato_amount)
+    }
+
+    /// Rate of the vibrato in Hz, 0.. (negative values are clamped to 0).
+    /// NaN is ignored.
+    pub fn vibrato_frequency(&self) -> f32 {
+        self.vibrato_frequency
+    }
+    pub fn set_vibrato_frequency(&mut self, vibrato_frequency: f32) {
+        self.vibrato_frequency =
+            math::clamp_or(vibrato_frequency, 0.0, f32::MAX, self.vibrato_frequency)
+    }
+
+    /// Depth of the aspiration noise modulation, 0.. (negative values
+    /// are clamped to 0). NaN is ignored.
+    pub fn aspiration_modulation(&self) -> f32 {
+        self.aspiration_modulation
+    }
+    pub fn set_aspiration_modulation(&mut self, depth: f32) {
+        self.aspiration_modulation =
+            math::clamp_or(depth, 0.0, f32::MAX, self.aspiration_modulation)
+    }
+
+    /// Waveform of the glottal pulses.
+    pub fn source(&self) -> &dyn GlottalSource {
+        self.source.as_ref()
+    }
+    /// Replaces the waveform of the glottal pulses from the next sample on.
+    pub fn set_source(&mut self, source: Box<dyn GlottalSource>) {
+        self.source = source
+    }
+
+    /// The current glottal cycle sampled at `points` evenly spaced phases
+    /// over 0..1 (excluding 1), as returned by the source, without
+    /// intensity and loudness applied. The state of the glottis is not
+    /// advanced; the source is sampled through a clone.
+    pub fn normalized_wavetable(&self, points: usize) -> Vec<f32> {
+        let mut source = self.source.clone();
+        (0..points)
+            .map(|k| source.sample(k as f32 / points as f32, &self.source_params))
+            .collect()
+    }
+
+    /// Like `normalized_wavetable`, scaled like the pulses of the
+    /// current cycle by intensity, loudness and the natural onset.
+    pub fn wavetable(&self, points: usize) -> Vec<f32> {
+        let gain = self.intensity * self.loudness * self.onset_gain();
+        let mut table = self.normalized_wavetable(points);
+        for x in &mut table {
+            *x *= gain;
+        }
+        table
+    }
+
+    /// Whether the last `step` started a new glottal cycle.
+    pub fn cycle_started(&self) -> bool {
+        self.cycle_started
+    }
+
+    /// Position within the current glottal cycle, 0..1.
+    pub fn phase(&self) -> f32 {
+        (self.time_in_waveform / self.waveform_length) % 1.0
+    }
+    /// Jumps to `phase` (wrapped to 0..1) of the current glottal cycle.
+    /// NaN and infinite values are ignored.
+    pub fn set_phase(&mut self, phase: f32) {
+        if !phase.is_finite() {
+            return;
+        }
+        self.time_in_waveform = phase.rem_euclid(1.0) * self.waveform_length;
+    }
+
+    /// Copies the voicing parameters and state of `leader`,
+    /// pitched `frequency_ratio` times higher.
+    pub(crate) fn follow(&mut self, leader: &Glottis, frequency_ratio: f32) {
+        self.always_voice = leader.always_voice;
+        self.auto_wobble = leader.auto_wobble;
+        self.vibrato_enabled = leader.vibrato_enabled;
+        self.tenseness_drift = leader.tenseness_drift;
+        self.is_touched = leader.is_touched;
+        self.target_tenseness = leader.target_tenseness;
+        self.target_frequency = leader.target_frequency * frequency_ratio;
+        self.vibrato_amount = leader.vibrato_amount;
+        self.vibrato_frequency = leader.vibrato_frequency;
+        self.max_frequency = leader.max_frequency;
+        self.frozen = leader.frozen;
+        self.aspiration_modulation = leader.aspiration_modulation;
+        self.natural_onset = leader.natural_onset;
+        self.loudness_mode = leader.loudness_mode;
+        if leader.loudness_mode == LoudnessMode::Manual {
+            self.loudness = leader.loudness;
+        }
+        let pre_aspiration_left = leader
+            .pre_aspiration_end
+            .saturating_sub(leader.sample_count);
+        self.pre_aspiration_end = self.sample_count + pre_aspiration_left;
+        self.intensity = leader.intensity;
+        self.smooth_frequency = leader.smooth_frequency * frequency_ratio;
+    }
+
     pub fn set_musical_note(&mut self, semitone: f32) {
-        const A4: f32 = 440.0;
-        self.target_frequency = A4 * 2.0_f32.powf(semitone * (1.0 / 12.0));
+        self.target_frequency = math::semitone_to_hz(semitone);
     }
 
     pub fn step(&mut self, lambda: f32) -> f32 {
-        let time = self.sample_count as f32 / self.sample_rate as f32;
+        let (pulse, aspiration) = self.step_components(lambda);
+        pulse + aspiration
+    }
+
+    /// Like `step`, but returns the glottal pulse and
+    /// the aspiration noise separately.
+    pub fn step_components(&mut self, lambda: f32) -> (f32, f32) {
+        let time = self.sample_count as f64 / self.sample_rate as f64;
+        self.advance_ramps();
 
-        if self.time_in_waveform > self.waveform_length {
+        self.cycle_started = self.time_in_waveform > self.waveform_length;
+        if self.cycle_started {
             self.time_in_waveform -= self.waveform_length;
             self.setup_waveform(lambda);
+            self.onset_cycle = (self.onset_cycle + 1).min(ONSET_CYCLES);
         }
 
-        let out1 = self.normalized_lf_waveform(self.time_in_waveform / self.waveform_length);
-        let asp_noise = (self.aspiration_noise_source)() as f32;
-        let aspiration1 = self.intensity
-            * (1.0 - self.target_tenseness.sqrt())
-            * self.get_noise_modulator()
-            * asp_noise;
-        let aspiration2 = aspiration1 * (0.2 + 0.02 * self.noise_generator.simplex(time * 1.99));
-        let result = out1 + aspiration2;
+        let mut out1 = self.waveform(self.time_in_waveform / self.waveform_length);
+        let asp_noise = match &mut self.aspiration_noise_source {
+            Some(noise) => noise.noise() as f32,
+            None => 0.0,
+        };
+        let modulation = self.aspiration_modulation;
+        let aspiration1 = if self.sample_count < self.pre_aspiration_end {
+            // no voicing, and aspiration as loud as for a fully breathy voice
+            out1 = 0.0;
+            self.noise_modulator_for(modulation, 0.0, 1.0) * asp_noise
+        } else {
+            self.intensity
+                * (1.0 - self.target_tenseness.sqrt())
+                * self.noise_modulator(modulation)
+                * asp_noise
+        };
+        let wobble = 0.02 * modulation * self.noise_generator.simplex((time * 1.99) as f32);
+        let aspiration2 = aspiration1 * (0.2 + wobble);
         self.sample_count += 1;
         self.time_in_waveform += 1.0 / self.sample_rate as f32;
-        result
+        (out1, aspiration2)
     }
 
     pub fn get_noise_modulator(&self) -> f32 {
-        let voiced =
-            0.1 + 0.2 * 0_f32.max((PI * 2.0 * self.time_in_waveform / self.waveform_length).sin());
-        self.target_tenseness * self.intensity * voiced
-            + (1.0 - self.target_tenseness * self.intensity) * 0.3
+        self.noise_modulator(1.0)
+    }
+
+    /// How far the glottis is open, 0..1, approximated as the positive
+    /// half of a sine over the glottal cycle: open in the first half,
+    /// closed in the second.
+    pub fn openness(&self) -> f32 {
+        0_f32.max((PI * 2.0 * self.time_in_waveform / self.waveform_length).sin())
+    }
+
+    /// Noise amplitude over the glottal cycle,
+    /// with the voiced pulsing scaled by `depth`.
+    fn noise_modulator(&self, depth: f32) -> f32 {
+        self.noise_modulator_for(depth, self.target_tenseness, self.intensity)
+    }
+
+    fn noise_modulator_for(&self, depth: f32, tenseness: f32, intensity: f32) -> f32 {
+        let pulse = self.openness();
+        let voiced = 0.1 + 0.2 * depth * pulse;
+        tenseness * intensity * voiced + (1.0 - tenseness * intensity) * 0.3
+    }
+
+    /// Stops the glottal pulses for `duration` seconds, replacing them
+    /// with breath noise. Intensity keeps following `is_touched` meanwhile.
+    pub fn pre_aspirate(&mut self, duration: f64) {
+        let samples = (duration.max(0.0) * self.sample_rate as f64).round() as u64;
+        self.pre_aspiration_end = self.sample_count + samples;
+    }
+
+    /// Moves `target_frequency` linearly to `to` over the next `samples`
+    /// steps, starting from the current pitch without vibrato.
+    pub fn ramp_frequency(&mut self, to: f32, samples: usize) {
+        self.frequency_ramp = Some(Ramp::new(self.smooth_frequency, to, samples));
+    }
+
+    /// Moves `target_tenseness` linearly to `to` over the next `samples` steps.
+    pub fn ramp_tenseness(&mut self, to: f32, samples: usize) {
+        self.tenseness_ramp = Some(Ramp::new(self.target_tenseness, to, samples));
+    }
+
+    /// Moves `intensity` linearly to `to` over the next `samples` steps.
+    /// Afterwards it follows `is_touched` again.
+    pub fn ramp_intensity(&mut self, to: f32, samples: usize) {
+        self.intensity_ramp = Some(Ramp::new(self.intensity, to, samples));
+    }
+
+    fn advance_ramps(&mut self) {
+        if let Some(ramp) = &mut self.frequency_ramp {
+            // the ramp replaces gliding
+            let frequency = ramp.next();
+            self.target_frequency = frequency;
+            self.smooth_frequency = frequency;
+        }
+        if let Some(ramp) = &mut self.tenseness_ramp {
+            self.target_tenseness = ramp.next();
+        }
+        if let Some(ramp) = &mut self.intensity_ramp {
+            self.intensity = ramp.next();
+        }
+    }
+
+    /// Moves time and the glottal cycle `samples` steps forward without
+    /// producing any output. Ramps progress as if the steps were taken.
+    pub fn skip(&mut self, samples: usize) {
+        self.sample_count += samples as u64;
+        let time_in_waveform =
+            self.time_in_waveform as f64 + samples as f64 / self.sample_rate as f64;
+        self.time_in_waveform = time_in_waveform.rem_euclid(self.waveform_length as f64) as f32;
+        if let Some(ramp) = &mut self.frequency_ramp {
+            let frequency = ramp.advance(samples);
+            self.target_frequency = frequency;
+            self.smooth_frequency = frequency;
+        }
+        if let Some(ramp) = &mut self.tenseness_ramp {
+            self.target_tenseness = ramp.advance(samples);
+        }
+        if let Some(ramp) = &mut self.intensity_ramp {
+            self.intensity = ramp.advance(samples);
+        }
     }
 
     pub fn adjust_parameters(&mut self, delta_time: f32) {
-        let delta = delta_time * self.sample_rate as f32 / 512.0;
-        let old_time = self.sample_count as f32 / self.sample_rate as f32;
-        let new_time = old_time + delta_time;
-        self.adjust_intensity(delta);
-        self.calculate_new_frequency(new_time, delta);
-        self.calculate_new_tenseness(new_time);
+        let old_time = self.sample_count as f64 / self.sample_rate as f64;
+        let new_time = old_time + delta_time as f64;
+        if self.is_touched && !self.was_touched && self.natural_onset && !self.frozen {
+            self.onset_cycle = 0;
+        }
+        self.was_touched = self.is_touched;
+        if self.frozen {
+            self.apply_targets();
+        } else {
+            if self.intensity_ramp.is_none() {
+                self.adjust_intensity(delta_time);
+            }
+            self.calculate_new_frequency(new_time, delta_time);
+            self.calculate_new_tenseness(new_time);
+        }
+        let finished = |ramp: &Option<Ramp>| ramp.is_some_and(|ramp| ramp.is_finished());
+        if finished(&self.frequency_ramp) {
+            self.frequency_ramp = None;
+        }
+        if finished(&self.tenseness_ramp) {
+            self.tenseness_ramp = None;
+        }
+        if finished(&self.intensity_ramp) {
+            self.intensity_ramp = None;
+        }
+    }
+
+    /// Moves intensity, frequency and tenseness to their targets instantly,
+    /// for the whole next block.
+    fn apply_targets(&mut self) {
+        if self.intensity_ramp.is_none() {
+            self.intensity = if self.is_touched || self.always_voice {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        self.old_vibrato = 0.0;
+        self.new_vibrato = 0.0;
+        self.smooth_frequency = self.target_frequency;
+        let frequency = self
+            .target_frequency
+            .max(MIN_FREQUENCY)
+            .min(self.max_frequency);
+        self.old_frequency = frequency;
+        self.new_frequency = frequency;
+        let tenseness = self.target_tenseness.max(0.0);
+        self.old_tenseness = tenseness;
+        self.new_tenseness = tenseness;
+        self.tenseness_offset = tenseness - self.target_tenseness;
     }
 
-    fn calculate_new_frequency(&mut self, time: f32, delta_time: f32) {
+    fn calculate_new_frequency(&mut self, time: f64, delta_time: f32) {
         if self.intensity == 0.0 {
             self.smooth_frequency = self.target_frequency;
         } else if self.target_frequency > self.smooth_frequency {
             self.smooth_frequency = self
                 .target_frequency
-                .min(self.smooth_frequency * (1.0 + 0.1 * delta_time));
+                .min(self.smooth_frequency * (1.0 + FREQUENCY_GLIDE_RATE * delta_time));
         } else if self.target_frequency < self.smooth_frequency {
             self.smooth_frequency = self
                 .target_frequency
-                .max(self.smooth_frequency / (1.0 + 0.1 * delta_time));
+                .max(self.smooth_frequency / (1.0 + FREQUENCY_GLIDE_RATE * delta_time));
         }
 
-        self.old_frequency = self.new_frequency;
-        self.new_frequency =
-            (self.smooth_frequency * (1.0 + self.calculate_vibrato(time))).max(10.0);
+        // a ramp moved the pitch away from the last block's estimate
+        self.old_frequency = if self.frequency_ramp.is_some() {
+            self.frequency_with_vibrato(self.new_vibrato)
+        } else {
+            self.new_frequency
+        };
+        self.old_vibrato = self.new_vibrato;
+        self.new_vibrato = self.calculate_vibrato(time);
+        self.new_frequency = self.frequency_with_vibrato(self.new_vibrato);
+    }
+
+    fn frequency_with_vibrato(&self, vibrato: f32) -> f32 {
+        (self.smooth_frequency * (1.0 + vibrato))
+            .max(MIN_FREQUENCY)
+            .min(self.max_frequency)
     }
 
-    fn calculate_new_tenseness(&mut self, time: f32) {
-        self.old_tenseness = self.new_tenseness;
-        self.new_tenseness = self.target_tenseness
-            + 0.1 * self.noise_generator.simplex(time * 0.46)
-            + 0.05 * self.noise_generator.simplex(time * 0.36);
+    fn calculate_new_tenseness(&mut self, time: f64) {
+        self.old_tenseness = if self.tenseness_ramp.is_some() {
+            self.target_tenseness + self.tenseness_offset
+        } else {
+            self.new_tenseness
+        };
+        self.new_tenseness = self.target_tenseness;
+        if self.tenseness_drift {
+            self.new_tenseness = self.new_tenseness
+                + 0.1 * self.noise_generator.simplex((time * 0.46) as f32)
+                + 0.05 * self.noise_generator.simplex((time * 0.36) as f32);
+        }
         self.new_tenseness = self.new_tenseness.max(0.0);
 
         if !self.is_touched && self.always_voice {
             // attack
             self.new_tenseness += (3.0 - self.target_tenseness) * (1.0 - self.intensity);
         }
+        self.tenseness_offset = self.new_tenseness - self.target_tenseness;
     }
 
-    fn adjust_intensity(&mut self, delta: f32) {
+    fn adjust_intensity(&mut self, delta_time: f32) {
         if self.is_touched || self.always_voice {
-            self.intensity += 0.13 * delta;
+            self.intensity += INTENSITY_ATTACK_RATE * delta_time;
         } else {
-            self.intensity -= 0.05 * delta;
+            self.intensity -= INTENSITY_RELEASE_RATE * delta_time;
         }
 
         self.intensity = self.intensity.clamp(0.0, 1.0);
     }
 
-    fn calculate_vibrato(&mut self, time: f32) -> f32 {
-        let mut vibrato = self.vibrato_amount * (PI * 2.0 * time * self.vibrato_frequency).sin();
-        vibrato += 0.02 * self.noise_generator.simplex(time * 4.07);
-        vibrato += 0.04 * self.noise_generator.simplex(time * 2.15);
+    fn calculate_vibrato(&mut self, time: f64) -> f32 {
+        if !self.vibrato_enabled {
+            return 0.0;
+        }
+        // the phase is wrapped in double precision to stay accurate in long renders
+        let phase = (time * self.vibrato_frequency as f64).fract() as f32;
+        let mut vibrato = self.vibrato_amount * (PI * 2.0 * phase).sin();
+        vibrato += 0.02 * self.noise_generator.simplex((time * 4.07) as f32);
+        vibrato += 0.04 * self.noise_generator.simplex((time * 2.15) as f32);
         if self.auto_wobble {
-            vibrato += 0.2 * self.noise_generator.simplex(time * 0.96);
-            vibrato += 0.4 * self.noise_generator.simplex(time * 0.5);
+            vibrato += 0.2 * self.noise_generator.simplex((time * 0.96) as f32);
+            vibrato += 0.4 * self.noise_generator.simplex((time * 0.5) as f32);
         }
         vibrato
     }
 
     fn setup_waveform(&mut self, lambda: f32) {
-        let frequency = interpolate(self.old_frequency, self.new_frequency, lambda);
-        let tenseness = interpolate(self.old_tenseness, self.new_tenseness, lambda);
-        self.waveform_length = 1.0 / frequency;
-        self.loudness = tenseness.max(0.0).powf(0.25);
-
-        let rd = (3.0 * (1.0 - tenseness)).clamp(0.5, 2.7);
-
-        // normalized to time = 1, Ee = 1
-        let ra = -0.01 + 0.048 * rd;
-
-        let rk = 0.224 + 0.118 * rd;
-        let rg = (rk / 4.0) * (0.5 + 1.2 * rk) / (0.11 * rd - ra * (0.5 + 1.2 * rk));
-
-        let ta = ra;
-        let tp = 1.0 / (2.0 * rg);
-        let te = tp + tp * rk;
-
-        let epsilon = 1.0 / ta;
-        let shift = (-epsilon * (1.0 - te)).exp();
-        let delta = 1.0 - shift; // divide by self to scale RHS
-
-        let rhs_integral = ((1.0 / epsilon) * (shift - 1.0) + (1.0 - te) * shift) / delta;
-        let total_lower_integral = rhs_integral - (te - tp) / 2.0;
-        let total_upper_integral = -total_lower_integral;
-
-        let omega = PI / tp;
-        let s = (omega * te).sin();
-
-        // need E0*e^(alpha*Te)*s = -1 (to meet the return at -1)
-        // and E0*e^(alpha*Tp/2) * Tp*2/pi = totalUpperIntegral
-        //             (our approximation of the integral up to Tp)
-        // writing x for e^alpha,
-        // have E0*x^Te*s = -1 and E0 * x^(Tp/2) * Tp*2/pi = totalUpperIntegral
-        // dividing the second by the first,
-        // letting y = x^(Tp/2 - Te),
-        // y * Tp*2 / (pi*s) = -totalUpperIntegral;
-
-        let y = -PI * s * total_upper_integral / (tp * 2.0);
-        let z = y.ln();
-        let alpha = z / (tp / 2.0 - te);
-        let e0 = -1.0 / (s * (alpha * te).exp());
-
-        self.alpha = alpha;
-        self.e0 = e0;
-        self.epsilon = epsilon;
-        self.shift = shift;
-        self.delta = delta;
-        self.te = te;
-        self.omega = omega;
-    }
-
-    fn normalized_lf_waveform(&self, t: f32) -> f32 {
-        let output = if t > self.te {
-            (-(-self.epsilon * (t - self.te)).exp() + self.shift) / self.delta
+        // ramps are followed sample by sample, with the block's modulation on top
+        let frequency = if self.frequency_ramp.is_some() {
+            self.frequency_with_vibrato(interpolate(self.old_vibrato, self.new_vibrato, lambda))
         } else {
-            self.e0 * (self.alpha * t).exp() * (self.omega * t).sin()
+            interpolate(self.old_frequency, self.new_frequency, lambda)
+        };
+        let mut tenseness = if self.tenseness_ramp.is_some() {
+            self.target_tenseness + self.tenseness_offset
+        } else {
+            interpolate(self.old_tenseness, self.new_tenseness, lambda)
+        };
+        if !tenseness.is_finite() {
+            tenseness = 0.0;
+        }
+        if frequency.is_finite() && frequency > 0.0 {
+            self.waveform_length = 1.0 / frequency;
+        }
+        match self.loudness_mode {
+            LoudnessMode::FollowTenseness => self.loudness = tenseness.max(0.0).powf(0.25),
+            LoudnessMode::Fixed(loudness) => self.loudness = loudness,
+            LoudnessMode::Manual => {}
+        }
+        self.source_params = SourceParams {
+            tenseness,
+            frequency: 1.0 / self.waveform_length,
         };
-        output * self.intensity * self.loudness
+    }
+
+    fn waveform(&mut self, t: f32) -> f32 {
+        let output = self.source.sample(t, &self.source_params);
+        output * self.intensity * self.loudness * self.onset_gain()
+    }
+
+    /// Amplitude of the current cycle during a natural onset, 1 otherwise.
+    fn onset_gain(&self) -> f32 {
+        if self.onset_cycle < ONSET_CYCLES {
+            (self.onset_cycle + 1) as f32 / (ONSET_CYCLES + 1) as f32
+        } else {
+            1.0
+        }
     }
 }
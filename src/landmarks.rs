@@ -0,0 +1,21 @@
+//! Articulatory landmarks along the vocal tract, as cell indices
+//! counted from the glottis (see `PinkTrombone::tongue_index`).
+
+use crate::tract::Tract;
+
+/// Number of cells in the oral tract.
+pub const TRACT_LEN: usize = Tract::N;
+/// First cell past the glottal end, whose diameter is fixed.
+pub const GLOTTIS_END: usize = Tract::GLOTTIS_END;
+/// First cell of the tongue blade.
+pub const BLADE_START: usize = Tract::BLADE_START;
+/// First cell of the tongue tip.
+pub const TIP_START: usize = Tract::TIP_START;
+/// First cell of the lips.
+pub const LIP_START: usize = Tract::LIP_START;
+/// Oral tract cell the nasal tract branches off at by default
+/// (see `PinkTrombone::set_nose_start`).
+pub const NOSE_START: usize = Tract::NOSE_START;
+/// Default number of cells in the nasal tract
+/// (see `PinkTrombone::set_nose_length`).
+pub const NOSE_LEN: usize = Tract::NOSE_LEN;
@@ -1,14 +1,54 @@
+mod agc;
+mod choir;
+mod config;
+mod constriction;
+mod dc_blocker;
+mod decimation;
+mod envelope;
+mod formants;
+mod gesture;
+mod glottal_source;
 mod glottis;
+pub mod landmarks;
 mod math;
 mod noise;
 mod noise_gen;
+mod params;
+mod pcm;
+mod phoneme;
+mod pitch;
+mod ramp;
 mod rng;
+#[cfg(feature = "rodio")]
+mod rodio_source;
+mod side_branch;
+mod sink;
+mod subglottis;
+pub mod testing;
+mod tilt;
 mod tract;
 mod tract_shaper;
 mod transient;
 mod trombone;
 mod turbulence;
+mod unison;
 
-pub use noise::NoiseSource;
-pub use trombone::PinkTrombone;
-pub use turbulence::TurbulencePoint;
+pub use agc::AgcSettings;
+pub use choir::PinkTromboneChoir;
+pub use config::{MorphCurve, PinkTromboneConfig};
+pub use constriction::Constriction;
+pub use decimation::DecimationMode;
+pub use gesture::Gesture;
+pub use glottal_source::{GlottalSource, GlottalSourceClone, LfSource, SourceParams};
+pub use glottis::{Glottis, LoudnessMode};
+pub use noise::{NoiseMode, NoiseSource};
+pub use params::Params;
+pub use phoneme::Phoneme;
+#[cfg(feature = "rodio")]
+pub use rodio_source::PinkTromboneSource;
+pub use sink::AudioSink;
+pub use tract::{NoseJunction, Tract};
+pub use tract_shaper::TractShaper;
+pub use transient::Transient;
+pub use trombone::{LengthMismatch, NonFinite, PinkTrombone};
+pub use turbulence::{TurbulenceDistribution, TurbulencePoint};
@@ -15,9 +15,43 @@ pub fn move_towards(current: f64, target: f64, amount_up: f64, amount_down: f64)
     }
 }
 
+/// Moves `current` the `approach` fraction of the way to `target`.
+pub fn smooth(current: f64, target: f64, approach: f64) -> f64 {
+    if approach >= 1.0 {
+        target
+    } else {
+        current + approach * (target - current)
+    }
+}
+
 pub fn sqr<T>(x: T) -> T
 where
     T: Mul<T, Output = T> + Copy,
 {
     x * x
 }
+
+const A4: f32 = 440.0;
+
+/// Frequency in Hz of the note `semitone` semitones from A4.
+pub fn semitone_to_hz(semitone: f32) -> f32 {
+    A4 * 2.0_f32.powf(semitone * (1.0 / 12.0))
+}
+
+pub fn hz_to_semitone(hz: f32) -> f32 {
+    12.0 * (hz / A4).log2()
+}
+
+/// `value` limited to `min..=max`, or `fallback` if `value` is NaN.
+pub fn clamp_or<T: PartialOrd + Copy>(value: T, min: T, max: T, fallback: T) -> T {
+    // only NaN is not comparable to itself
+    if value.partial_cmp(&value).is_none() {
+        fallback
+    } else if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        value
+    }
+}
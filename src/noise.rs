@@ -4,34 +4,136 @@ pub trait NoiseSource<T> {
     fn noise(&mut self) -> T;
 }
 
+/// Default length of the looped white noise buffers, in samples.
+pub const DEFAULT_LOOP_SIZE: usize = 0x8000;
+
+/// Where the white noise feeding aspiration and frication comes from.
+///
+/// Both modes are deterministic for a given RNG state, but they consume it
+/// differently, so switching modes changes the generated noise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseMode {
+    /// Replay a buffer of this many samples, filled from the RNG up front.
+    /// Cheap per sample, but repeats, which can be audible on long
+    /// sustained fricatives if the buffer is short.
+    Looped(usize),
+    /// Generate fresh noise every sample from a generator seeded from the RNG.
+    /// Never repeats, at the cost of a few integer operations per sample.
+    ///
+    /// The RNG passed in is only borrowed while the noise is set up, so
+    /// rather than drawing from it every sample, a single value is drawn
+    /// to seed an internal SplitMix64 generator. The noise is thus
+    /// reproducible from the RNG state at that point alone, and the RNG is
+    /// left free for other uses, but its own distribution does not carry
+    /// over to the noise.
+    Live,
+}
+
+impl Default for NoiseMode {
+    fn default() -> NoiseMode {
+        NoiseMode::Looped(DEFAULT_LOOP_SIZE)
+    }
+}
+
+/// Band-pass filtered white noise.
+#[derive(Clone)]
+pub struct FilteredNoise {
+    white_noise: WhiteNoise,
+    filter: BiquadIirFilter,
+}
+
+impl NoiseSource<f64> for FilteredNoise {
+    fn noise(&mut self) -> f64 {
+        self.filter.filter(self.white_noise.noise())
+    }
+}
+
+impl FilteredNoise {
+    /// Replaces the band-pass filter, e.g. after a sample rate change.
+    pub fn set_bandpass(&mut self, f0: f64, q: f64, sample_rate: u32) {
+        self.filter = new_bandpass_filter(f0, q, sample_rate);
+    }
+
+    /// Regenerates the white noise from `rng`, keeping the noise mode
+    /// (and loop size) and the filter state. Looped noise restarts
+    /// from the beginning of the loop.
+    pub fn reseed(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        self.refill(rng);
+        if let WhiteNoise::Looped(buffer) = &mut self.white_noise {
+            buffer.current_index = 0;
+        }
+    }
+
+    /// Overwrites the white noise with new values from `rng` without
+    /// allocating. The filter state and the position in the noise loop
+    /// are kept, so the noise continues without a click.
+    pub fn refill(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        match &mut self.white_noise {
+            WhiteNoise::Looped(buffer) => buffer.refill(rng),
+            WhiteNoise::Live(generator) => *generator = LiveNoise::from_rng(rng),
+        }
+    }
+}
+
 pub fn new_filtered_noise_source(
     f0: f64,
     q: f64,
     sample_rate: u32,
-    loop_size: usize,
+    mode: NoiseMode,
     rng: &mut dyn NoiseSource<f64>,
-) -> Box<dyn FnMut() -> f64 + Send + 'static> {
-    let mut white_noise = new_looped_white_noise(loop_size, rng);
-    let mut filter = new_bandpass_filter(f0, q, sample_rate);
-    Box::new(move || filter.filter(white_noise.noise()))
+) -> FilteredNoise {
+    let white_noise = match mode {
+        NoiseMode::Looped(loop_size) => WhiteNoise::Looped(new_looped_white_noise(loop_size, rng)),
+        NoiseMode::Live => WhiteNoise::Live(LiveNoise::from_rng(rng)),
+    };
+    FilteredNoise {
+        white_noise,
+        filter: new_bandpass_filter(f0, q, sample_rate),
+    }
 }
 
-fn new_looped_white_noise(
-    loop_size: usize,
-    rng: &mut dyn NoiseSource<f64>,
-) -> impl NoiseSource<f64> {
-    let noise = (0..loop_size).map(|_| 2.0 * rng.noise() - 1.0).collect();
-    LoopedNoiseBuffer {
-        noise,
-        current_index: 0,
+/// White noise in -1..1
+#[derive(Clone)]
+enum WhiteNoise {
+    Looped(LoopedNoiseBuffer),
+    Live(LiveNoise),
+}
+
+impl NoiseSource<f64> for WhiteNoise {
+    fn noise(&mut self) -> f64 {
+        match self {
+            WhiteNoise::Looped(buffer) => buffer.noise(),
+            WhiteNoise::Live(generator) => 2.0 * generator.noise() - 1.0,
+        }
+    }
+}
+
+fn new_looped_white_noise(loop_size: usize, rng: &mut dyn NoiseSource<f64>) -> LoopedNoiseBuffer {
+    if loop_size == 0 {
+        panic!("noise loop size must be > 0");
     }
+    let mut buffer = LoopedNoiseBuffer {
+        noise: vec![0.0; loop_size],
+        current_index: 0,
+    };
+    buffer.refill(rng);
+    buffer
 }
 
+#[derive(Clone)]
 struct LoopedNoiseBuffer {
     noise: Vec<f64>,
     current_index: usize,
 }
 
+impl LoopedNoiseBuffer {
+    fn refill(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        for x in &mut self.noise {
+            *x = 2.0 * rng.noise() - 1.0;
+        }
+    }
+}
+
 impl NoiseSource<f64> for LoopedNoiseBuffer {
     fn noise(&mut self) -> f64 {
         if self.current_index >= self.noise.len() {
@@ -43,11 +145,77 @@ impl NoiseSource<f64> for LoopedNoiseBuffer {
     }
 }
 
+/// Noise sources seeded from independent streams,
+/// so that their textures are decorrelated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseStream {
+    Aspiration,
+    Frication,
+}
+
+impl NoiseStream {
+    /// Draws a master seed for `NoiseStream::rng` from `rng`.
+    fn master_seed(rng: &mut dyn NoiseSource<f64>) -> u64 {
+        (rng.noise() * (1u64 << 53) as f64) as u64
+    }
+
+    /// Generator for this stream, derived from `master_seed`. Every stream
+    /// gets different values regardless of the order they are drawn in.
+    pub fn rng(self, master_seed: u64) -> LiveNoise {
+        // hashing keeps the streams of similar master seeds apart
+        let stream = LiveNoise::with_seed(master_seed ^ (self as u64 + 1));
+        LiveNoise::with_seed(stream.state_hash())
+    }
+
+    /// Generators for the aspiration and frication streams, seeded by
+    /// a single draw from `rng`. Only that draw is taken from `rng`, so
+    /// deterministic sources like `testing::ConstantNoise` do not shape
+    /// the noise; see `PinkTrombone::with_noise_sources` for those.
+    pub fn split(rng: &mut dyn NoiseSource<f64>) -> (LiveNoise, LiveNoise) {
+        let master_seed = NoiseStream::master_seed(rng);
+        (
+            NoiseStream::Aspiration.rng(master_seed),
+            NoiseStream::Frication.rng(master_seed),
+        )
+    }
+}
+
+/// SplitMix64 generator producing uniform values in 0..1
+#[derive(Clone)]
+pub struct LiveNoise {
+    state: u64,
+}
+
+impl LiveNoise {
+    pub(crate) fn from_rng(rng: &mut dyn NoiseSource<f64>) -> LiveNoise {
+        LiveNoise::with_seed((rng.noise() * (1u64 << 53) as f64) as u64)
+    }
+
+    pub fn with_seed(seed: u64) -> LiveNoise {
+        LiveNoise { state: seed }
+    }
+
+    /// SplitMix64 output for the current state, as a full 64-bit value.
+    fn state_hash(&self) -> u64 {
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl NoiseSource<f64> for LiveNoise {
+    fn noise(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        (self.state_hash() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
 pub trait Filter {
     fn filter(&mut self, x: f64) -> f64;
 }
 
-pub fn new_bandpass_filter(f0: f64, q: f64, sample_rate: u32) -> impl Filter {
+pub fn new_bandpass_filter(f0: f64, q: f64, sample_rate: u32) -> BiquadIirFilter {
     let w0 = 2.0 * PI * f0 / sample_rate as f64;
     let alpha = w0.sin() / (2.0 * q);
     let b0 = alpha;
@@ -59,7 +227,8 @@ pub fn new_bandpass_filter(f0: f64, q: f64, sample_rate: u32) -> impl Filter {
     BiquadIirFilter::new(b0, b1, b2, a0, a1, a2)
 }
 
-struct BiquadIirFilter {
+#[derive(Clone)]
+pub struct BiquadIirFilter {
     nb0: f64,
     nb1: f64,
     nb2: f64,
@@ -99,3 +268,48 @@ impl Filter for BiquadIirFilter {
         y
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::xorshift;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn noise(mode: NoiseMode, rng: &mut dyn NoiseSource<f64>) -> Vec<f64> {
+        let mut source = new_filtered_noise_source(500.0, 0.5, SAMPLE_RATE, mode, rng);
+        (0..SAMPLE_RATE).map(|_| source.noise()).collect()
+    }
+
+    #[test]
+    fn live_noise_does_not_repeat() {
+        let mut rng = xorshift::XorShift128::new(9452);
+        let looped = noise(NoiseMode::Looped(1000), &mut rng);
+        let live = noise(NoiseMode::Live, &mut rng);
+        let tail = SAMPLE_RATE as usize / 2;
+        let repeats = |signal: &[f64]| {
+            signal[tail..]
+                .iter()
+                .zip(&signal[tail - 1000..])
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        };
+        assert!(repeats(&looped));
+        assert!(!repeats(&live));
+        assert!(live.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn live_noise_draws_a_single_seed() {
+        let mut rng = xorshift::XorShift128::new(9452);
+        let first = noise(NoiseMode::Live, &mut rng);
+        let after_first = rng.noise();
+
+        let mut rng = xorshift::XorShift128::new(9452);
+        let second = noise(NoiseMode::Live, &mut rng);
+        assert_eq!(first, second);
+        // only the seed was taken from the RNG
+        let mut reference = xorshift::XorShift128::new(9452);
+        reference.noise();
+        assert_eq!(after_first, reference.noise());
+    }
+}
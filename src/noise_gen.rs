@@ -1,4 +1,5 @@
 #[derive(Copy, Clone)]
+#[allow(dead_code)]
 struct Grad {
     pub x: f32, pub y: f32, pub z: f32
 }
@@ -30,6 +31,7 @@ const P: [u8; 256] = [
     236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
 ];
 
+#[derive(Clone)]
 pub struct NoiseGenerator {
     grad_p: [Grad; 512],
     perm: [u8; 512],
@@ -41,11 +43,11 @@ impl NoiseGenerator {
             seed |= seed << 8;
         }
 
-        for i in 0..256 {
+        for (i, &p) in P.iter().enumerate() {
             let v = if (i & 1) == 1 {
-                P[i] ^ (seed & 255) as u8
+                p ^ (seed & 255) as u8
             } else {
-                P[i] ^ ((seed >> 8) & 255) as u8
+                p ^ ((seed >> 8) & 255) as u8
             };
             self.perm[i + 256] = v;
             self.perm[i] = v;
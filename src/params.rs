@@ -0,0 +1,32 @@
+/// A set of parameter changes to apply together,
+/// see `PinkTrombone::set_params_pending`. `None` fields are left unchanged.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    pub target_frequency: Option<f32>,
+    pub target_tenseness: Option<f32>,
+    pub intensity: Option<f32>,
+    pub tongue_index: Option<f64>,
+    pub tongue_diameter: Option<f64>,
+    pub velum_open: Option<bool>,
+}
+
+impl Params {
+    /// Overrides fields of `self` with the fields set in `newer`.
+    pub fn merge(&mut self, newer: Params) {
+        fn merge_field<T>(field: &mut Option<T>, newer: Option<T>) {
+            if newer.is_some() {
+                *field = newer;
+            }
+        }
+        merge_field(&mut self.target_frequency, newer.target_frequency);
+        merge_field(&mut self.target_tenseness, newer.target_tenseness);
+        merge_field(&mut self.intensity, newer.intensity);
+        merge_field(&mut self.tongue_index, newer.tongue_index);
+        merge_field(&mut self.tongue_diameter, newer.tongue_diameter);
+        merge_field(&mut self.velum_open, newer.velum_open);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Params::default()
+    }
+}
@@ -0,0 +1,66 @@
+/// Converts a sample in -1..1 to 16-bit PCM, rounding to the nearest
+/// value. Samples outside of the range are clipped.
+pub fn to_i16(x: f32) -> i16 {
+    let x = if x.is_nan() { 0.0 } else { x.clamp(-1.0, 1.0) };
+    (x * i16::MAX as f32).round() as i16
+}
+
+/// G.711 companding works on 14-bit magnitudes,
+/// with this bias added so that all segments start at a power of two
+const MULAW_BIAS: i32 = 0x84;
+const MULAW_CLIP: i32 = 32635;
+
+/// Converts a sample in -1..1 to 8-bit G.711 mu-law.
+/// Samples outside of the range are clipped.
+pub fn to_mulaw(x: f32) -> u8 {
+    let pcm = to_i16(x) as i32;
+    let sign = if pcm < 0 { 0x80 } else { 0 };
+    let magnitude = pcm.abs().min(MULAW_CLIP) + MULAW_BIAS;
+    // segment: position of the highest bit above the 7 lowest ones
+    let exponent = 31 - ((magnitude >> 7) as u32).leading_zeros();
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) as i32 | mantissa) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_mulaw(byte: u8) -> f32 {
+        let byte = !byte as i32;
+        let exponent = (byte >> 4) & 0x07;
+        let mantissa = byte & 0x0F;
+        let magnitude = (((mantissa << 3) + MULAW_BIAS) << exponent) - MULAW_BIAS;
+        let pcm = if byte & 0x80 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        };
+        pcm as f32 / i16::MAX as f32
+    }
+
+    #[test]
+    fn i16_rounds_and_clips() {
+        assert_eq!(to_i16(0.0), 0);
+        assert_eq!(to_i16(1.0), i16::MAX);
+        assert_eq!(to_i16(-1.0), -i16::MAX);
+        assert_eq!(to_i16(2.0), i16::MAX);
+        assert_eq!(to_i16(-2.0), -i16::MAX);
+        assert_eq!(to_i16(0.6 / i16::MAX as f32), 1);
+        assert_eq!(to_i16(0.4 / i16::MAX as f32), 0);
+    }
+
+    #[test]
+    fn mulaw_matches_g711() {
+        assert_eq!(to_mulaw(0.0), 0xFF);
+        assert_eq!(to_mulaw(1.0), 0x80);
+        assert_eq!(to_mulaw(-1.0), 0x00);
+        assert_eq!(to_mulaw(5.0), 0x80);
+        for k in -100..=100 {
+            let x = k as f32 / 100.0;
+            let error = (from_mulaw(to_mulaw(x)) - x).abs();
+            // quantization steps grow with the magnitude, up to 1/32 of it
+            assert!(error <= x.abs() / 32.0 + 0.001, "{} {}", x, error);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+/// Approximate vowel presets for the tongue position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phoneme {
+    /// as in "father"
+    A,
+    /// as in "bed"
+    E,
+    /// as in "see"
+    I,
+    /// as in "law"
+    O,
+    /// as in "boot" (lip rounding is not modelled)
+    U,
+    /// as in "about", the neutral tongue position
+    Schwa,
+}
+
+impl Phoneme {
+    /// Tongue index and diameter for this phoneme.
+    pub fn tongue(self) -> (f64, f64) {
+        match self {
+            Phoneme::A => (13.5, 2.9),
+            Phoneme::E => (24.0, 2.6),
+            Phoneme::I => (27.5, 2.1),
+            Phoneme::O => (13.0, 2.4),
+            Phoneme::U => (21.5, 2.1),
+            Phoneme::Schwa => (12.9, 2.43),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/// pitch range searched, in Hz
+const MIN_FREQUENCY: f64 = 50.0;
+const MAX_FREQUENCY: f64 = 1000.0;
+/// mean square below which the input is treated as silence
+const SILENCE: f64 = 1e-8;
+/// how periodic the input must be to have a pitch, 0..1
+const MIN_CLARITY: f64 = 0.6;
+/// peaks this close to the highest one are candidates for the period,
+/// the shortest of them winning to avoid picking multiples of it
+const PEAK_THRESHOLD: f64 = 0.9;
+
+/// Fundamental frequency of `samples` in Hz, from the peaks of their
+/// normalized square difference function (an autocorrelation normalized
+/// by the energy of the overlapping parts), or `None` if they are silent
+/// or not periodic enough.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mean_square =
+        samples.iter().map(|&x| (x as f64).powi(2)).sum::<f64>() / samples.len() as f64;
+    if mean_square.is_nan() || mean_square < SILENCE {
+        return None;
+    }
+    let min_lag = ((sample_rate as f64 / MAX_FREQUENCY).floor() as usize).max(1);
+    // at least two periods have to fit into the input
+    let max_lag = ((sample_rate as f64 / MIN_FREQUENCY).ceil() as usize).min(samples.len() / 2);
+    if max_lag < min_lag + 2 {
+        return None;
+    }
+
+    let nsdf: Vec<f64> = (0..=max_lag + 1)
+        .map(|lag| {
+            let (mut correlation, mut energy) = (0.0, 0.0);
+            for (&a, &b) in samples.iter().zip(&samples[lag..]) {
+                let (a, b) = (a as f64, b as f64);
+                correlation += a * b;
+                energy += a * a + b * b;
+            }
+            if energy > 0.0 {
+                2.0 * correlation / energy
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let peaks: Vec<usize> = (min_lag..=max_lag)
+        .filter(|&lag| nsdf[lag] > nsdf[lag - 1] && nsdf[lag] >= nsdf[lag + 1])
+        .collect();
+    let highest = peaks.iter().map(|&lag| nsdf[lag]).fold(f64::MIN, f64::max);
+    if highest < MIN_CLARITY {
+        return None;
+    }
+    let lag = *peaks
+        .iter()
+        .find(|&&lag| nsdf[lag] >= PEAK_THRESHOLD * highest)?;
+
+    // parabolic interpolation between lags
+    let (left, center, right) = (nsdf[lag - 1], nsdf[lag], nsdf[lag + 1]);
+    let curvature = left - 2.0 * center + right;
+    let offset = if curvature < 0.0 {
+        0.5 * (left - right) / curvature
+    } else {
+        0.0
+    };
+    Some((sample_rate as f64 / (lag as f64 + offset)) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{noise::NoiseSource, rng::xorshift::XorShift128};
+
+    #[test]
+    fn detects_tones_and_rejects_noise() {
+        const SAMPLE_RATE: u32 = 48000;
+        for &frequency in &[55.0, 140.0, 441.0, 900.0] {
+            let tone: Vec<f32> = (0..4800)
+                .map(|i| {
+                    let phase =
+                        2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE as f32;
+                    // a harmonic makes the second period peak as high
+                    0.5 * phase.sin() + 0.3 * (2.0 * phase).sin()
+                })
+                .collect();
+            let detected = detect(&tone, SAMPLE_RATE).unwrap();
+            assert!(
+                (detected / frequency - 1.0).abs() < 0.002,
+                "{} {}",
+                frequency,
+                detected
+            );
+        }
+
+        assert_eq!(detect(&[0.0; 4800], SAMPLE_RATE), None);
+        assert_eq!(detect(&[], SAMPLE_RATE), None);
+        let mut random = XorShift128::new(1);
+        let noise: Vec<f32> = (0..4800).map(|_| random.noise() as f32 - 0.5).collect();
+        assert_eq!(detect(&noise, SAMPLE_RATE), None);
+    }
+}
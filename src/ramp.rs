@@ -0,0 +1,40 @@
+/// Linear change of a parameter over a number of samples,
+/// advanced once per sample.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Ramp {
+    to: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl Ramp {
+    pub fn new(from: f32, to: f32, samples: usize) -> Ramp {
+        let step = if samples == 0 {
+            0.0
+        } else {
+            (to - from) / samples as f32
+        };
+        Ramp {
+            to,
+            step,
+            remaining: samples,
+        }
+    }
+
+    /// Value for the next sample. It reaches `to` exactly
+    /// on the last sample of the ramp and stays there.
+    pub fn next(&mut self) -> f32 {
+        self.advance(1)
+    }
+
+    /// Value `samples` samples on.
+    pub fn advance(&mut self, samples: usize) -> f32 {
+        self.remaining = self.remaining.saturating_sub(samples);
+        // computed from the end rather than accumulated, to not drift
+        self.to - self.step * self.remaining as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.remaining == 0
+    }
+}
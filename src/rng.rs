@@ -33,7 +33,7 @@ pub mod xorshift {
                 base: BaseGenerator {
                     bit_buffer: 0,
                     bit_count: 0,
-                    seed: seed,
+                    seed,
                 },
                 x: 0,
                 y: 0,
@@ -58,7 +58,7 @@ pub mod xorshift {
             self.bytes_available = false;
 
             let result = to_f64(tx.overflowing_add(ty).0);
-            assert!(result >= 0.0 && result <= 1.0);
+            assert!((0.0..=1.0).contains(&result));
             result
         }
 
@@ -95,7 +95,7 @@ pub mod xorshift {
 
     fn to_f64(mut value: u64) -> f64 {
         value = (value >> 12) | 0x3FF0000000000000;
-        let res = unsafe { std::mem::transmute::<u64, f64>(value) };
+        let res = f64::from_bits(value);
         res - 1.0
     }
 
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rodio::Source;
+
+use crate::trombone::PinkTrombone;
+
+/// Samples synthesized at once, and the latency added by the buffering.
+const BUFFER_LEN: usize = 512;
+
+/// A `PinkTrombone` playing as an endless `rodio::Source`,
+/// e.g. with `OutputStreamHandle::play_raw`.
+///
+/// Keep a `trombone` handle to change its parameters while it plays.
+/// The voice is repeated on each of the `channels`.
+pub struct PinkTromboneSource {
+    trombone: Arc<Mutex<PinkTrombone>>,
+    sample_rate: u32,
+    channels: u16,
+    buffer: [f32; BUFFER_LEN],
+    buffer_pos: usize,
+    /// channel the next sample is for
+    channel: u16,
+}
+
+impl PinkTromboneSource {
+    /// Plays `trombone` in mono.
+    pub fn new(trombone: PinkTrombone) -> PinkTromboneSource {
+        PinkTromboneSource::with_channels(trombone, 1)
+    }
+
+    /// Plays `trombone` on each of `channels` interleaved channels.
+    /// Panics if `channels` is 0.
+    pub fn with_channels(trombone: PinkTrombone, channels: u16) -> PinkTromboneSource {
+        if channels == 0 {
+            panic!("source needs at least one channel");
+        }
+        PinkTromboneSource {
+            sample_rate: trombone.sample_rate(),
+            trombone: Arc::new(Mutex::new(trombone)),
+            channels,
+            buffer: [0.0; BUFFER_LEN],
+            buffer_pos: BUFFER_LEN,
+            channel: 0,
+        }
+    }
+
+    /// Shared handle to the synthesizer, to change its parameters
+    /// after the source has been handed over to rodio.
+    pub fn trombone(&self) -> Arc<Mutex<PinkTrombone>> {
+        self.trombone.clone()
+    }
+
+    /// Locks the synthesizer to change its parameters,
+    /// e.g. `source.lock().set_musical_note(3.0)`.
+    /// Playback waits while the lock is held.
+    pub fn lock(&self) -> MutexGuard<'_, PinkTrombone> {
+        self.trombone.lock().unwrap()
+    }
+}
+
+impl Iterator for PinkTromboneSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.buffer_pos == BUFFER_LEN {
+            self.trombone.lock().unwrap().synthesize(&mut self.buffer);
+            self.buffer_pos = 0;
+        }
+        let sample = self.buffer[self.buffer_pos];
+        self.channel += 1;
+        if self.channel == self.channels {
+            self.channel = 0;
+            self.buffer_pos += 1;
+        }
+        Some(sample)
+    }
+}
+
+impl Source for PinkTromboneSource {
+    /// The channels and the sample rate never change.
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, SAMPLE_RATE};
+
+    #[test]
+    fn repeats_samples_on_each_channel() {
+        let trombone = testing::trombone();
+        let mut mono = PinkTromboneSource::new(trombone.clone());
+        let stereo = PinkTromboneSource::with_channels(trombone, 2);
+        assert_eq!(stereo.channels(), 2);
+        assert_eq!(stereo.sample_rate(), SAMPLE_RATE);
+
+        let samples: Vec<f32> = stereo.take(4 * BUFFER_LEN).collect();
+        for pair in samples.chunks(2) {
+            assert_eq!(pair[0], pair[1]);
+            assert_eq!(Some(pair[0]), mono.next());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::math::{interpolate, smooth, sqr};
+
+/// Number of cells in the side branch. As a quarter-wave resonator it
+/// produces a spectral zero at a twentieth of the tract sample rate,
+/// 4.8kHz when synthesizing at 48kHz.
+pub const LEN: usize = 5;
+const DIAMETER: f64 = 0.8;
+/// the far end is closed, with some loss
+const END_REFLECTION: f64 = 0.9;
+
+/// Closed uniform tube branching off the main tract,
+/// modelling the piriform fossa.
+#[derive(Clone)]
+pub struct SideBranch {
+    /// main tract junction the branch is coupled at,
+    /// between cells `position - 1` and `position`
+    pub position: usize,
+    right: [f64; LEN],
+    left: [f64; LEN],
+    reflection_left: f64,
+    reflection_right: f64,
+    reflection_branch: f64,
+    new_reflection_left: f64,
+    new_reflection_right: f64,
+    new_reflection_branch: f64,
+}
+
+impl SideBranch {
+    pub fn new(position: usize) -> SideBranch {
+        SideBranch {
+            position,
+            right: [0.0; LEN],
+            left: [0.0; LEN],
+            reflection_left: 0.0,
+            reflection_right: 0.0,
+            reflection_branch: 0.0,
+            new_reflection_left: 0.0,
+            new_reflection_right: 0.0,
+            new_reflection_branch: 0.0,
+        }
+    }
+
+    /// Clears the travelling waves.
+    pub fn silence(&mut self) {
+        self.right = [0.0; LEN];
+        self.left = [0.0; LEN];
+    }
+
+    /// Same scattering as the nasal junction, see `Tract::step`.
+    pub fn calculate_junction_reflections(&mut self, diameter: &[f64], approach: f64) {
+        self.reflection_left = self.new_reflection_left;
+        self.reflection_right = self.new_reflection_right;
+        self.reflection_branch = self.new_reflection_branch;
+
+        let branch_a = sqr(DIAMETER);
+        let a0 = sqr(diameter[self.position - 1]);
+        let a1 = sqr(diameter[self.position]);
+        let sum = a0 + a1 + branch_a;
+
+        let (left, right, branch) = if sum.abs() > 1e-6 {
+            (
+                (2.0 * a0 - sum) / sum,
+                (2.0 * a1 - sum) / sum,
+                (2.0 * branch_a - sum) / sum,
+            )
+        } else {
+            (1.0, 1.0, 1.0)
+        };
+        self.new_reflection_left = smooth(self.reflection_left, left, approach);
+        self.new_reflection_right = smooth(self.reflection_right, right, approach);
+        self.new_reflection_branch = smooth(self.reflection_branch, branch, approach);
+    }
+
+    /// Scatters the waves arriving at the junction and advances the branch.
+    /// `right` and `left` are the main tract waves arriving from the glottis
+    /// and lip sides. Returns the waves leaving towards the glottis and lips.
+    pub fn step(&mut self, right: f64, left: f64, lambda: f64) -> (f64, f64) {
+        let r = interpolate(self.reflection_left, self.new_reflection_left, lambda);
+        let to_glottis = r * right + (1.0 + r) * (self.left[0] + left);
+        let r = interpolate(self.reflection_right, self.new_reflection_right, lambda);
+        let to_lips = r * left + (1.0 + r) * (right + self.left[0]);
+        let r = interpolate(self.reflection_branch, self.new_reflection_branch, lambda);
+        let into_branch = r * self.left[0] + (1.0 + r) * (left + right);
+
+        let end = self.right[LEN - 1] * END_REFLECTION;
+        self.right.copy_within(0..LEN - 1, 1);
+        self.right[0] = into_branch;
+        self.left.copy_within(1..LEN, 0);
+        self.left[LEN - 1] = end;
+
+        (to_glottis, to_lips)
+    }
+}
@@ -0,0 +1,27 @@
+/// Destination of synthesized samples, see `PinkTrombone::synthesize_to`,
+/// e.g. to write straight into the buffers of an audio plugin host.
+pub trait AudioSink {
+    fn push(&mut self, sample: f32);
+}
+
+impl AudioSink for Vec<f32> {
+    fn push(&mut self, sample: f32) {
+        Vec::push(self, sample)
+    }
+}
+
+/// Writes to the start of the slice and advances it past the written
+/// sample, like `std::io::Write` for `&mut [u8]`.
+/// Panics when the slice is full.
+impl AudioSink for &mut [f32] {
+    fn push(&mut self, sample: f32) {
+        let buf = std::mem::take(self);
+        match buf.split_first_mut() {
+            Some((first, rest)) => {
+                *first = sample;
+                *self = rest;
+            }
+            None => panic!("audio sink slice is full"),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+/// Number of cells of the subglottal tube. Closed by the glottis at the
+/// top and opening into the lungs at the bottom, it resonates as a
+/// quarter-wave tube at odd multiples of the tract sample rate divided by
+/// 160: 600Hz, 1.8kHz and 3kHz when synthesizing at 48kHz, close to the
+/// subglottal resonances of an adult trachea.
+pub const LEN: usize = 40;
+/// the lungs absorb much of the sound reaching them
+const LUNG_REFLECTION: f64 = -0.7;
+
+/// Uniform tube below the glottis, modelling the trachea, coupled to the
+/// oral tract through the glottal opening.
+#[derive(Clone)]
+pub struct Subglottis {
+    /// waves travelling from the glottis (index 0) down to the lungs
+    down: [f64; LEN],
+    /// waves travelling up from the lungs, arriving at the glottis at index 0
+    up: [f64; LEN],
+}
+
+impl Subglottis {
+    pub fn new() -> Subglottis {
+        Subglottis {
+            down: [0.0; LEN],
+            up: [0.0; LEN],
+        }
+    }
+
+    /// Clears the travelling waves.
+    pub fn silence(&mut self) {
+        self.down = [0.0; LEN];
+        self.up = [0.0; LEN];
+    }
+
+    /// Scatters the waves arriving at the glottis and advances the tube.
+    /// `from_tract` is the oral tract wave arriving at the glottis, and
+    /// `transmission` (0 for a closed glottis) the fraction of the waves
+    /// passing through it. Returns the wave transmitted up into the tract.
+    ///
+    /// The subglottal side reflects what it does not transmit, so with the
+    /// tract side reflecting at most `1 - transmission`, the junction never
+    /// adds energy.
+    pub fn step(&mut self, from_tract: f64, transmission: f64) -> f64 {
+        let arriving = self.up[0];
+        let into_tract = transmission * arriving;
+        let into_lungs = transmission * from_tract + (1.0 - transmission) * arriving;
+
+        let end = self.down[LEN - 1] * LUNG_REFLECTION;
+        self.down.copy_within(0..LEN - 1, 1);
+        self.down[0] = into_lungs;
+        self.up.copy_within(1..LEN, 0);
+        self.up[LEN - 1] = end;
+
+        into_tract
+    }
+}
+
+impl Default for Subglottis {
+    fn default() -> Subglottis {
+        Subglottis::new()
+    }
+}
@@ -0,0 +1,62 @@
+//! Deterministic noise sources for reproducible tests,
+//! with no dependency on a random number generator.
+//!
+//! `NoiseSource<f64>` values are expected to be in 0..1, and are mapped to
+//! white noise samples in -1..1, so a `ConstantNoise(0.5)` silences
+//! aspiration and frication noise entirely, leaving only the
+//! deterministic glottal waveform. Pass these sources to
+//! `PinkTrombone::with_noise_sources`: the other constructors only take
+//! a seed from their RNG.
+
+use crate::noise::NoiseSource;
+#[cfg(test)]
+use crate::{rng::xorshift, PinkTrombone};
+
+/// Sample rate the crate's own tests synthesize at.
+#[cfg(test)]
+pub(crate) const SAMPLE_RATE: u32 = 48000;
+/// Seed of the synthesizers made by `trombone`.
+#[cfg(test)]
+pub(crate) const SEED: u16 = 9452;
+
+/// The synthesizer most of the crate's own tests start from: at
+/// `SAMPLE_RATE`, with both the noise and the drift seeded from `SEED`.
+#[cfg(test)]
+pub(crate) fn trombone() -> PinkTrombone {
+    let mut random = xorshift::XorShift128::new(SEED.into());
+    PinkTrombone::new(SAMPLE_RATE, &mut random, SEED)
+}
+
+/// Always returns the same value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConstantNoise(pub f64);
+
+impl NoiseSource<f64> for ConstantNoise {
+    fn noise(&mut self) -> f64 {
+        self.0
+    }
+}
+
+/// Cycles through a fixed sequence of values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceNoise {
+    values: Vec<f64>,
+    index: usize,
+}
+
+impl SequenceNoise {
+    pub fn new(values: Vec<f64>) -> SequenceNoise {
+        if values.is_empty() {
+            panic!("noise sequence must not be empty");
+        }
+        SequenceNoise { values, index: 0 }
+    }
+}
+
+impl NoiseSource<f64> for SequenceNoise {
+    fn noise(&mut self) -> f64 {
+        let value = self.values[self.index];
+        self.index = (self.index + 1) % self.values.len();
+        value
+    }
+}
@@ -0,0 +1,119 @@
+use std::f64::consts::PI;
+
+use crate::noise::Filter;
+
+/// the tilt is matched between these frequencies, in Hz
+const LOW_FREQUENCY: f64 = 500.0;
+const HIGH_FREQUENCY: f64 = 4000.0;
+/// a one-pole filter can not get steeper than 6 dB/octave
+pub const MAX_TILT: f32 = 5.9;
+
+/// First order filter approximating a constant spectral tilt.
+///
+/// Negative tilt uses a one-pole low-pass filter, positive tilt uses its
+/// inverse (a one-zero pre-emphasis filter). In both cases the pole/zero
+/// is placed so that the response changes by the requested amount per
+/// octave on average between 500 Hz and 4 kHz. The gain at DC is 1.
+#[derive(Clone)]
+pub struct SpectralTilt {
+    db_per_octave: f32,
+    coefficient: f64,
+    state: f64,
+}
+
+impl SpectralTilt {
+    pub fn new() -> SpectralTilt {
+        SpectralTilt {
+            db_per_octave: 0.0,
+            coefficient: 0.0,
+            state: 0.0,
+        }
+    }
+
+    pub fn db_per_octave(&self) -> f32 {
+        self.db_per_octave
+    }
+
+    pub fn set_db_per_octave(&mut self, db_per_octave: f32, sample_rate: u32) {
+        let db_per_octave = db_per_octave.clamp(-MAX_TILT, MAX_TILT);
+        let octaves = (HIGH_FREQUENCY / LOW_FREQUENCY).log2();
+        let target = db_per_octave.abs() as f64 * octaves;
+        let low = LOW_FREQUENCY.min(sample_rate as f64 / 2.0);
+        let high = HIGH_FREQUENCY.min(sample_rate as f64 / 2.0);
+
+        // attenuation of the low-pass filter grows with the coefficient
+        let (mut lo, mut hi) = (0.0, 1.0 - 1e-9);
+        for _ in 0..60 {
+            let mid = (lo + hi) / 2.0;
+            let attenuation =
+                low_pass_gain_db(mid, low, sample_rate) - low_pass_gain_db(mid, high, sample_rate);
+            if attenuation < target {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        self.db_per_octave = db_per_octave;
+        self.coefficient = if db_per_octave == 0.0 { 0.0 } else { lo };
+        self.state = 0.0;
+    }
+}
+
+fn low_pass_gain_db(coefficient: f64, frequency: f64, sample_rate: u32) -> f64 {
+    let w = 2.0 * PI * frequency / sample_rate as f64;
+    let a = coefficient;
+    let power = (1.0 - a) * (1.0 - a) / (1.0 - 2.0 * a * w.cos() + a * a);
+    10.0 * power.log10()
+}
+
+impl Filter for SpectralTilt {
+    fn filter(&mut self, x: f64) -> f64 {
+        let a = self.coefficient;
+        if self.db_per_octave < 0.0 {
+            // y[n] = (1 - a) x[n] + a y[n-1]
+            let y = (1.0 - a) * x + a * self.state;
+            self.state = y;
+            y
+        } else if self.db_per_octave > 0.0 {
+            // y[n] = (x[n] - a x[n-1]) / (1 - a)
+            let y = (x - a * self.state) / (1.0 - a);
+            self.state = x;
+            y
+        } else {
+            x
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn tone_gain_db(tilt: &mut SpectralTilt, frequency: f64) -> f64 {
+        let len = SAMPLE_RATE as usize / 10;
+        let mut peak: f64 = 0.0;
+        for n in 0..len {
+            let x = (2.0 * PI * frequency * n as f64 / SAMPLE_RATE as f64).sin();
+            let y = tilt.filter(x);
+            if n > len / 2 {
+                peak = peak.max(y.abs());
+            }
+        }
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn tilt_matches_requested_slope() {
+        for &db_per_octave in &[-3.0, 3.0] {
+            let mut tilt = SpectralTilt::new();
+            tilt.set_db_per_octave(db_per_octave, SAMPLE_RATE);
+            let low = tone_gain_db(&mut tilt, LOW_FREQUENCY);
+            let high = tone_gain_db(&mut tilt, HIGH_FREQUENCY);
+            let slope = (high - low) / (HIGH_FREQUENCY / LOW_FREQUENCY).log2();
+            assert!((slope - db_per_octave as f64).abs() < 0.1, "{}", slope);
+        }
+    }
+}
@@ -1,16 +1,25 @@
+use crate::formants;
 use crate::glottis::Glottis;
-use crate::math::{interpolate, sqr};
-use crate::noise::{self, NoiseSource};
+use crate::math::{interpolate, smooth, sqr};
+use crate::noise::{self, FilteredNoise, NoiseMode, NoiseSource};
+use crate::side_branch::SideBranch;
+use crate::subglottis::Subglottis;
 use crate::transient::Transient;
-use crate::turbulence::TurbulencePoint;
-
+use crate::turbulence::{TurbulenceDistribution, TurbulencePoint};
+
+/// Digital waveguide model of the oral and nasal tracts.
+///
+/// Runs at twice the output sample rate. Call `calculate_new_block_parameters`
+/// after changing diameters, then `step` with any excitation signal.
+/// The owned `glottis` only modulates turbulence noise.
+#[derive(Clone)]
 pub struct Tract {
     pub glottis: Glottis,
     sample_rate: u32,
-    frication_noise_source: Box<dyn FnMut() -> f64 + Send + 'static>,
+    frication_noise_source: FilteredNoise,
 
-    sample_count: usize,
-    pub time: f32,
+    pub(crate) sample_count: u64,
+    pub(crate) time: f64,
 
     left: [f64; Tract::N],
     right: [f64; Tract::N],
@@ -22,16 +31,32 @@ pub struct Tract {
     /// vocal tract cell diameters
     pub diameter: [f64; Tract::N],
 
-    pub transients: Vec<Transient>,
+    pub(crate) transients: Vec<Transient>,
     pub turbulence_points: Vec<TurbulencePoint>,
 
-    nose_right: [f64; NOSE_LEN],
-    nose_left: [f64; NOSE_LEN],
-    nose_junction_output_right: [f64; NOSE_LEN],
-    nose_junction_output_left: [f64; NOSE_LEN + 1],
-    nose_reflection: [f64; NOSE_LEN],
-    pub nose_diameter: [f64; NOSE_LEN],
-    nose_max_amplitude: [f64; NOSE_LEN],
+    nose_right: Vec<f64>,
+    nose_left: Vec<f64>,
+    nose_junction_output_right: Vec<f64>,
+    nose_junction_output_left: Vec<f64>,
+    nose_reflection: Vec<f64>,
+    /// nasal tract cell diameters, the first one being the velum opening
+    pub(crate) nose_diameter: Vec<f64>,
+    nose_max_amplitude: Vec<f64>,
+
+    /// oral tract cell the nasal tract branches off at
+    nose_start: usize,
+
+    /// piriform fossa side branch, `None` when disabled
+    piriform: Option<SideBranch>,
+    piriform_position: usize,
+
+    /// subglottal tube coupled through the glottis, `None` when disabled
+    subglottis: Option<Subglottis>,
+
+    /// oral component of the output of the last step
+    pub last_lip_output: f64,
+    /// nasal component of the output of the last step
+    pub last_nose_output: f64,
 
     reflection_left: f64,
     reflection_right: f64,
@@ -39,18 +64,81 @@ pub struct Tract {
     new_reflection_right: f64,
     reflection_nose: f64,
     new_reflection_nose: f64,
+    reflection_smoothing: f64,
+    fricative_attack_time: f32,
+    fricative_release_time: f32,
+    pub turbulence_distribution: TurbulenceDistribution,
+    /// when true, the glottal reflection follows the glottis tenseness
+    /// instead of being constant
+    pub dynamic_glottal_reflection: bool,
+}
+
+/// Reflection coefficients of the three-way junction
+/// where the nasal tract branches off.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NoseJunction {
+    /// seen by the wave travelling back towards the glottis
+    pub left: f64,
+    /// seen by the wave travelling forward towards the lips
+    pub right: f64,
+    /// seen by the wave coming back out of the nasal tract
+    pub nose: f64,
+}
+
+/// Travelling wave state of the tract, used to probe the tract
+/// without disturbing synthesis.
+#[derive(Clone)]
+struct WaveState {
+    sample_count: u64,
+    time: f64,
+    left: [f64; N],
+    right: [f64; N],
+    junction_output_right: [f64; N],
+    justion_output_left: [f64; N + 1],
+    max_amplitude: [f64; N],
+    nose_right: Vec<f64>,
+    nose_left: Vec<f64>,
+    nose_junction_output_right: Vec<f64>,
+    nose_junction_output_left: Vec<f64>,
+    nose_max_amplitude: Vec<f64>,
+    piriform: Option<SideBranch>,
+    subglottis: Option<Subglottis>,
+    last_lip_output: f64,
+    last_nose_output: f64,
 }
 
 const N: usize = Tract::N;
 
 const GLOTTAL_REFLECTION: f64 = 0.75;
+const FRICATIVE_ATTACK_TIME: f32 = 0.1; // seconds
+/// center frequency and Q of the frication noise band
+const FRICATION_FREQUENCY: f64 = 1000.0;
+const FRICATION_Q: f64 = 0.5;
 const LIP_REFLECTION: f64 = -0.85;
 
+/// just above the glottal end, where the piriform fossae branch off
+const PIRIFORM_POSITION: usize = 3;
+
 const NOSE_LEN: usize = 28;
 const NOSE_START: usize = N - NOSE_LEN + 1;
 
+// `step` indexes the tract and nose relying on these
+const _: () = {
+    assert!(PIRIFORM_POSITION > 0 && PIRIFORM_POSITION < Tract::GLOTTIS_END);
+    assert!(Tract::GLOTTIS_END <= Tract::BLADE_START);
+    assert!(Tract::BLADE_START < Tract::TIP_START);
+    assert!(Tract::TIP_START < Tract::LIP_START);
+    assert!(Tract::LIP_START < N);
+    assert!(NOSE_LEN >= 2);
+    assert!(NOSE_START > 0 && NOSE_START + 1 < N);
+    assert!(NOSE_START + NOSE_LEN <= N + 1);
+    assert!(NOSE_START != PIRIFORM_POSITION);
+};
+
 impl Tract {
     pub const N: usize = 44;
+    /// cells below this one belong to the glottal end and keep a fixed diameter
+    pub const GLOTTIS_END: usize = 7;
     pub const BLADE_START: usize = 10;
     pub const TIP_START: usize = 32;
     pub const LIP_START: usize = 39;
@@ -58,6 +146,15 @@ impl Tract {
     pub const NOSE_LEN: usize = NOSE_LEN;
 
     pub fn new(glottis: Glottis, sample_rate: u32, rng: &mut dyn NoiseSource<f64>) -> Tract {
+        Tract::with_noise_mode(glottis, sample_rate, rng, NoiseMode::default())
+    }
+
+    pub fn with_noise_mode(
+        glottis: Glottis,
+        sample_rate: u32,
+        rng: &mut dyn NoiseSource<f64>,
+        mode: NoiseMode,
+    ) -> Tract {
         if sample_rate == 0 {
             panic!("sample_rate must be > 0")
         };
@@ -65,13 +162,7 @@ impl Tract {
         Tract {
             glottis,
             sample_rate,
-            frication_noise_source: noise::new_filtered_noise_source(
-                1000.0,
-                0.5,
-                sample_rate,
-                0x8000,
-                rng,
-            ),
+            frication_noise_source: Tract::new_frication_noise_source(sample_rate, mode, rng),
 
             transients: Vec::new(),
             turbulence_points: Vec::new(),
@@ -87,81 +178,452 @@ impl Tract {
             max_amplitude: [0.0; Tract::N],
             diameter: [0.0; Tract::N],
 
-            nose_right: [0.0; NOSE_LEN],
-            nose_left: [0.0; NOSE_LEN],
-            nose_junction_output_right: [0.0; NOSE_LEN],
-            nose_junction_output_left: [0.0; NOSE_LEN + 1],
-            nose_reflection: [0.0; NOSE_LEN],
-            nose_diameter: [0.0; NOSE_LEN],
-            nose_max_amplitude: [0.0; NOSE_LEN],
+            nose_right: vec![0.0; NOSE_LEN],
+            nose_left: vec![0.0; NOSE_LEN],
+            nose_junction_output_right: vec![0.0; NOSE_LEN],
+            nose_junction_output_left: vec![0.0; NOSE_LEN + 1],
+            nose_reflection: vec![0.0; NOSE_LEN],
+            nose_diameter: vec![0.0; NOSE_LEN],
+            nose_max_amplitude: vec![0.0; NOSE_LEN],
+            nose_start: NOSE_START,
+            piriform: None,
+            piriform_position: PIRIFORM_POSITION,
+            subglottis: None,
+            last_lip_output: 0.0,
+            last_nose_output: 0.0,
             reflection_left: 0.0,
             reflection_right: 0.0,
             new_reflection_left: 0.0,
             new_reflection_right: 0.0,
             reflection_nose: 0.0,
             new_reflection_nose: 0.0,
+            reflection_smoothing: 0.0,
+            fricative_attack_time: FRICATIVE_ATTACK_TIME,
+            fricative_release_time: FRICATIVE_ATTACK_TIME,
+            turbulence_distribution: TurbulenceDistribution::default(),
+            dynamic_glottal_reflection: false,
         }
     }
 
-    pub fn calculate_nose_reflections(&mut self) {
-        let mut a = [0.0; NOSE_LEN];
-        for i in 0..NOSE_LEN {
-            a[i] = 1e-6_f64.max(sqr(self.nose_diameter[i]));
+    fn new_frication_noise_source(
+        sample_rate: u32,
+        mode: NoiseMode,
+        rng: &mut dyn NoiseSource<f64>,
+    ) -> FilteredNoise {
+        noise::new_filtered_noise_source(FRICATION_FREQUENCY, FRICATION_Q, sample_rate, mode, rng)
+    }
+
+    /// Replaces the frication noise with a new one generated according to `mode`.
+    pub fn set_noise_mode(&mut self, mode: NoiseMode, rng: &mut dyn NoiseSource<f64>) {
+        self.frication_noise_source =
+            Tract::new_frication_noise_source(self.sample_rate, mode, rng);
+    }
+
+    /// Regenerates the frication noise from `rng`.
+    pub fn reseed(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        self.frication_noise_source.reseed(rng);
+    }
+
+    /// Overwrites the frication noise with new values from `rng` in place,
+    /// continuing from the same filter state and loop position.
+    pub fn refill_noise(&mut self, rng: &mut dyn NoiseSource<f64>) {
+        self.frication_noise_source.refill(rng);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Changes the rate the tract is stepped at, keeping `time` continuous.
+    /// Panics if `sample_rate` is 0.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate == 0 {
+            panic!("sample_rate must be > 0")
+        };
+        self.sample_count = (self.time * sample_rate as f64).round() as u64;
+        self.sample_rate = sample_rate;
+        self.time = self.sample_count as f64 / sample_rate as f64;
+        self.frication_noise_source
+            .set_bandpass(FRICATION_FREQUENCY, FRICATION_Q, sample_rate);
+    }
+
+    /// Time elapsed since construction, in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Number of tract samples generated since construction.
+    pub fn sample_count(&self) -> u64 {
+        self.sample_count
+    }
+
+    /// Moves `time` forward to `time` seconds without stepping,
+    /// leaving the waves in the tract as they are.
+    pub fn skip_to(&mut self, time: f64) {
+        let sample_count = (time * self.sample_rate as f64).round() as u64;
+        if sample_count > self.sample_count {
+            self.sample_count = sample_count;
+            self.time = sample_count as f64 / self.sample_rate as f64;
+        }
+    }
+
+    /// Response of the tract in its current shape to a unit impulse,
+    /// at the tract sample rate.
+    ///
+    /// Reflections are frozen at their latest values, and transients
+    /// and turbulence noise are disabled during the probe.
+    /// The tract state is restored afterwards.
+    pub fn impulse_response(&mut self, len: usize) -> Vec<f32> {
+        let saved = self.wave_state();
+        let nose_len = self.nose_len();
+        let transients = std::mem::take(&mut self.transients);
+        let turbulence_points = std::mem::take(&mut self.turbulence_points);
+
+        self.set_wave_state(WaveState {
+            left: [0.0; N],
+            right: [0.0; N],
+            junction_output_right: [0.0; N],
+            justion_output_left: [0.0; N + 1],
+            max_amplitude: [0.0; N],
+            nose_right: vec![0.0; nose_len],
+            nose_left: vec![0.0; nose_len],
+            nose_junction_output_right: vec![0.0; nose_len],
+            nose_junction_output_left: vec![0.0; nose_len + 1],
+            nose_max_amplitude: vec![0.0; nose_len],
+            piriform: saved.piriform.clone().map(|mut branch| {
+                branch.silence();
+                branch
+            }),
+            subglottis: saved.subglottis.clone().map(|mut subglottis| {
+                subglottis.silence();
+                subglottis
+            }),
+            ..saved.clone()
+        });
+        let response = (0..len)
+            .map(|i| self.step(if i == 0 { 1.0 } else { 0.0 }, 1.0))
+            .collect();
+
+        self.set_wave_state(saved);
+        self.transients = transients;
+        self.turbulence_points = turbulence_points;
+        response
+    }
+
+    fn wave_state(&self) -> WaveState {
+        WaveState {
+            sample_count: self.sample_count,
+            time: self.time,
+            left: self.left,
+            right: self.right,
+            junction_output_right: self.junction_output_right,
+            justion_output_left: self.justion_output_left,
+            max_amplitude: self.max_amplitude,
+            nose_right: self.nose_right.clone(),
+            nose_left: self.nose_left.clone(),
+            nose_junction_output_right: self.nose_junction_output_right.clone(),
+            nose_junction_output_left: self.nose_junction_output_left.clone(),
+            nose_max_amplitude: self.nose_max_amplitude.clone(),
+            piriform: self.piriform.clone(),
+            subglottis: self.subglottis.clone(),
+            last_lip_output: self.last_lip_output,
+            last_nose_output: self.last_nose_output,
+        }
+    }
+
+    fn set_wave_state(&mut self, state: WaveState) {
+        self.sample_count = state.sample_count;
+        self.time = state.time;
+        self.left = state.left;
+        self.right = state.right;
+        self.junction_output_right = state.junction_output_right;
+        self.justion_output_left = state.justion_output_left;
+        self.max_amplitude = state.max_amplitude;
+        self.nose_right = state.nose_right;
+        self.nose_left = state.nose_left;
+        self.nose_junction_output_right = state.nose_junction_output_right;
+        self.nose_junction_output_left = state.nose_junction_output_left;
+        self.nose_max_amplitude = state.nose_max_amplitude;
+        self.piriform = state.piriform;
+        self.subglottis = state.subglottis;
+        self.last_lip_output = state.last_lip_output;
+        self.last_nose_output = state.last_nose_output;
+    }
+
+    /// Transients currently sounding.
+    pub fn transients(&self) -> &[Transient] {
+        &self.transients
+    }
+
+    /// Slowly decaying peak amplitude over all oral and nasal tract cells.
+    pub fn max_amplitude(&self) -> f64 {
+        self.max_amplitude
+            .iter()
+            .chain(self.nose_max_amplitude.iter())
+            .fold(0.0, |max, &a| max.max(a))
+    }
+
+    /// Number of cells in the nasal tract.
+    pub fn nose_len(&self) -> usize {
+        self.nose_diameter.len()
+    }
+
+    /// Oral tract cell the nasal tract branches off at.
+    pub fn nose_start(&self) -> usize {
+        self.nose_start
+    }
+    /// Moves the velar coupling point of the nasal tract.
+    pub fn set_nose_start(&mut self, nose_start: usize) {
+        if nose_start == 0 || nose_start + 1 >= N {
+            panic!("nasal tract must branch off within the oral tract");
+        }
+        if self.piriform.is_some() && nose_start == self.piriform_position {
+            panic!("nasal tract must not branch off at the piriform fossa");
         }
-        for i in 1..NOSE_LEN {
+        self.nose_start = nose_start;
+    }
+
+    /// Nasal tract cell diameters, the first one being the velum opening.
+    pub fn nose_diameter(&self) -> &[f64] {
+        &self.nose_diameter
+    }
+
+    /// Changes the number of cells in the nasal tract.
+    /// Silences the nasal tract, and resets its diameters to 0.
+    pub fn set_nose_len(&mut self, nose_len: usize) {
+        if nose_len < 2 {
+            panic!("nasal tract must have at least 2 cells");
+        }
+        self.nose_right = vec![0.0; nose_len];
+        self.nose_left = vec![0.0; nose_len];
+        self.nose_junction_output_right = vec![0.0; nose_len];
+        self.nose_junction_output_left = vec![0.0; nose_len + 1];
+        self.nose_reflection = vec![0.0; nose_len];
+        self.nose_diameter = vec![0.0; nose_len];
+        self.nose_max_amplitude = vec![0.0; nose_len];
+    }
+
+    pub fn calculate_nose_reflections(&mut self) {
+        let a: Vec<f64> = self
+            .nose_diameter
+            .iter()
+            .map(|&d| 1e-6_f64.max(sqr(d)))
+            .collect();
+        for i in 1..a.len() {
             self.nose_reflection[i] = assert_volume((a[i - 1] - a[i]) / (a[i - 1] + a[i]));
         }
     }
 
-    pub fn calculate_new_block_parameters(&mut self) {
-        self.calculate_main_tract_reflections();
-        self.calculate_nose_junction_reflections();
+    pub fn piriform_enabled(&self) -> bool {
+        self.piriform.is_some()
+    }
+    /// Enables or disables the piriform fossa side branch.
+    /// Enabling starts the branch silent.
+    pub fn set_piriform_enabled(&mut self, enabled: bool) {
+        if enabled == self.piriform_enabled() {
+            return;
+        }
+        if enabled && self.piriform_position == self.nose_start {
+            panic!("piriform fossa must not branch off at the velum");
+        }
+        self.piriform = if enabled {
+            let mut branch = SideBranch::new(self.piriform_position);
+            branch.calculate_junction_reflections(&self.diameter, 1.0);
+            Some(branch)
+        } else {
+            None
+        };
+    }
+
+    pub fn subglottal_coupling(&self) -> bool {
+        self.subglottis.is_some()
+    }
+    /// Enables or disables the subglottal tube.
+    /// Enabling starts the tube silent.
+    pub fn set_subglottal_coupling(&mut self, enabled: bool) {
+        if enabled != self.subglottal_coupling() {
+            self.subglottis = enabled.then(Subglottis::new);
+        }
+    }
+
+    /// Main tract junction the piriform fossa side branch couples at,
+    /// between cells `position - 1` and `position`.
+    pub fn piriform_position(&self) -> usize {
+        self.piriform_position
+    }
+    pub fn set_piriform_position(&mut self, position: usize) {
+        if position == 0 || position >= N || position == self.nose_start {
+            panic!("piriform position must be within the tract and away from the velum");
+        }
+        self.piriform_position = position;
+        if let Some(branch) = &mut self.piriform {
+            branch.position = position;
+            branch.calculate_junction_reflections(&self.diameter, 1.0);
+        }
+    }
+
+    /// Reflection coefficients between oral tract cells `i - 1` and `i`
+    /// at the start of the current block. Index 0 is unused.
+    pub fn reflection(&self) -> &[f64] {
+        &self.reflection
+    }
+
+    /// Reflection coefficients at the end of the current block;
+    /// `step` interpolates from `reflection` to these.
+    pub fn new_reflection(&self) -> &[f64] {
+        &self.new_reflection
+    }
+
+    /// Reflection coefficients between nasal tract cells `i - 1` and `i`.
+    /// Index 0 is unused.
+    pub fn nose_reflection(&self) -> &[f64] {
+        &self.nose_reflection
+    }
+
+    /// Nasal junction reflection coefficients at the start of the current block.
+    pub fn nose_junction(&self) -> NoseJunction {
+        NoseJunction {
+            left: self.reflection_left,
+            right: self.reflection_right,
+            nose: self.reflection_nose,
+        }
+    }
+
+    /// Nasal junction reflection coefficients at the end of the current block.
+    pub fn new_nose_junction(&self) -> NoseJunction {
+        NoseJunction {
+            left: self.new_reflection_left,
+            right: self.new_reflection_right,
+            nose: self.new_reflection_nose,
+        }
+    }
+
+    /// How long reflection coefficient changes are smoothed over, in seconds.
+    pub fn reflection_smoothing(&self) -> f64 {
+        self.reflection_smoothing
+    }
+    /// Smooths abrupt diameter changes beyond the per-block interpolation:
+    /// reflection coefficients approach their targets exponentially
+    /// with this time constant. 0 (the default) disables smoothing.
+    pub fn set_reflection_smoothing(&mut self, seconds: f64) {
+        self.reflection_smoothing = seconds.max(0.0);
+    }
+
+    /// How long turbulence noise takes to fade in, in seconds.
+    pub fn fricative_attack_time(&self) -> f32 {
+        self.fricative_attack_time
+    }
+    /// Times below `f32::EPSILON`, including 0 and NaN, are raised to it,
+    /// as the fade divides by them.
+    pub fn set_fricative_attack_time(&mut self, seconds: f32) {
+        self.fricative_attack_time = seconds.max(f32::EPSILON);
+    }
+
+    /// How long turbulence noise takes to fade out, in seconds.
+    pub fn fricative_release_time(&self) -> f32 {
+        self.fricative_release_time
+    }
+    /// Limited like `set_fricative_attack_time`.
+    pub fn set_fricative_release_time(&mut self, seconds: f32) {
+        self.fricative_release_time = seconds.max(f32::EPSILON);
+    }
+
+    /// `delta_time` is the duration of the block in seconds.
+    pub fn calculate_new_block_parameters(&mut self, delta_time: f64) {
+        let approach = if self.reflection_smoothing > 0.0 {
+            (delta_time / self.reflection_smoothing).min(1.0)
+        } else {
+            1.0
+        };
+        self.calculate_main_tract_reflections(approach);
+        self.calculate_nose_junction_reflections(approach);
+        if let Some(branch) = &mut self.piriform {
+            branch.calculate_junction_reflections(&self.diameter, approach);
+        }
     }
 
-    fn calculate_main_tract_reflections(&mut self) {
+    fn calculate_main_tract_reflections(&mut self, approach: f64) {
         let mut a = [0.0; Tract::N];
-        for i in 0..Tract::N {
-            a[i] = sqr(self.diameter[i]);
+        for (a, &d) in a.iter_mut().zip(self.diameter.iter()) {
+            *a = sqr(d);
         }
         for i in 1..Tract::N {
             self.reflection[i] = self.new_reflection[i];
             let sum = a[i - 1] + a[i];
-            self.new_reflection[i] = if sum.abs() > 1e-6 {
+            let target = if sum.abs() > 1e-6 {
                 (a[i - 1] - a[i]) / sum
             } else {
                 1.0
             };
+            self.new_reflection[i] = smooth(self.reflection[i], target, approach);
         }
     }
 
-    fn calculate_nose_junction_reflections(&mut self) {
+    fn calculate_nose_junction_reflections(&mut self, approach: f64) {
         self.reflection_left = self.new_reflection_left;
         self.reflection_right = self.new_reflection_right;
         self.reflection_nose = self.new_reflection_nose;
 
         let velum_a = sqr(self.nose_diameter[0]);
-        let an0 = sqr(self.diameter[NOSE_START]);
-        let an1 = sqr(self.diameter[NOSE_START + 1]);
+        let an0 = sqr(self.diameter[self.nose_start]);
+        let an1 = sqr(self.diameter[self.nose_start + 1]);
         let sum = an0 + an1 + velum_a;
 
-        if sum.abs() > 1e-6 {
-            self.new_reflection_left = (2.0 * an0 - sum) / sum;
-            self.new_reflection_right = (2.0 * an1 - sum) / sum;
-            self.new_reflection_nose = (2.0 * velum_a - sum) / sum;
+        let (left, right, nose) = if sum.abs() > 1e-6 {
+            (
+                (2.0 * an0 - sum) / sum,
+                (2.0 * an1 - sum) / sum,
+                (2.0 * velum_a - sum) / sum,
+            )
         } else {
-            self.new_reflection_left = 1.0;
-            self.new_reflection_right = 1.0;
-            self.new_reflection_nose = 1.0;
+            (1.0, 1.0, 1.0)
+        };
+        self.new_reflection_left = smooth(self.reflection_left, left, approach);
+        self.new_reflection_right = smooth(self.reflection_right, right, approach);
+        self.new_reflection_nose = smooth(self.reflection_nose, nose, approach);
+    }
+
+    /// Reflection of the sound returning to the glottis.
+    fn glottal_reflection(&self) -> f64 {
+        if self.dynamic_glottal_reflection {
+            let tenseness = self.glottis.new_tenseness.clamp(0.0, 1.0) as f64;
+            -0.8 + 1.6 * tenseness
+        } else {
+            GLOTTAL_REFLECTION
         }
     }
 
+    /// Magnitude in dB of the transfer function from the glottis to the
+    /// lips at each of `frequencies` in Hz, for the latest reflection
+    /// coefficients of the oral tract, see `formants::transfer_db`.
+    pub fn spectrum(&self, frequencies: &[f32]) -> Vec<f32> {
+        frequencies
+            .iter()
+            .map(|&frequency| {
+                formants::transfer_db(
+                    &self.new_reflection,
+                    self.glottal_reflection(),
+                    LIP_REFLECTION,
+                    self.sample_rate,
+                    frequency as f64,
+                ) as f32
+            })
+            .collect()
+    }
+
     pub fn step(&mut self, glottal_output: f64, lambda: f64) -> f32 {
         // mouth
         self.process_transients();
         self.add_turbulence_noise();
 
-        // self.glottalReflection = -0.8 + 1.6 * self.glottis.newTenseness;
-        self.junction_output_right[0] = self.left[0] * GLOTTAL_REFLECTION + glottal_output;
+        let glottal_reflection = self.glottal_reflection();
+        let mut glottal_return = self.left[0] * glottal_reflection;
+        if let Some(subglottis) = &mut self.subglottis {
+            // the glottis passes what it does not reflect while open
+            let openness = self.glottis.openness() as f64;
+            let transmission = openness * (1.0 - glottal_reflection.abs());
+            glottal_return += subglottis.step(self.left[0], transmission);
+        }
+        self.junction_output_right[0] = glottal_return + glottal_output;
         self.justion_output_left[N] = self.right[N - 1] * LIP_REFLECTION;
 
         for i in 1..N {
@@ -172,7 +634,7 @@ impl Tract {
         }
 
         // now at junction with nose
-        let i = NOSE_START;
+        let i = self.nose_start;
         let r = interpolate(self.reflection_left, self.new_reflection_left, lambda);
         self.justion_output_left[i] =
             assert_volume(r * self.right[i - 1] + (1.0 + r) * (self.nose_left[0] + self.left[i]));
@@ -183,6 +645,13 @@ impl Tract {
         self.nose_junction_output_right[0] =
             assert_volume(r * self.nose_left[0] + (1.0 + r) * (self.left[i] + self.right[i - 1]));
 
+        if let Some(branch) = &mut self.piriform {
+            let i = branch.position;
+            let (left, right) = branch.step(self.right[i - 1], self.left[i], lambda);
+            self.justion_output_left[i] = assert_volume(left);
+            self.junction_output_right[i] = assert_volume(right);
+        }
+
         for i in 0..N {
             let right = self.junction_output_right[i] * 0.999;
             let left = self.justion_output_left[i + 1] * 0.999;
@@ -199,15 +668,16 @@ impl Tract {
         let lip_output = self.right[N - 1];
 
         // nose
-        self.nose_junction_output_left[NOSE_LEN] = self.nose_right[NOSE_LEN - 1] * LIP_REFLECTION;
+        let nose_len = self.nose_len();
+        self.nose_junction_output_left[nose_len] = self.nose_right[nose_len - 1] * LIP_REFLECTION;
 
-        for i in 1..NOSE_LEN {
+        for i in 1..nose_len {
             let w = self.nose_reflection[i] * (self.nose_right[i - 1] + self.nose_left[i]);
             self.nose_junction_output_right[i] = assert_volume(self.nose_right[i - 1] - w);
             self.nose_junction_output_left[i] = assert_volume(self.nose_left[i] + w);
         }
 
-        for i in 0..NOSE_LEN {
+        for i in 0..nose_len {
             let right = self.nose_junction_output_right[i];
             let left = self.nose_junction_output_left[i + 1];
             self.nose_right[i] = right;
@@ -217,10 +687,13 @@ impl Tract {
             self.nose_max_amplitude[i] = self.nose_max_amplitude[i].max(amplitude);
         }
 
-        let nose_output = self.nose_right[NOSE_LEN - 1];
+        let nose_output = self.nose_right[nose_len - 1];
+
+        self.last_lip_output = lip_output;
+        self.last_nose_output = nose_output;
 
         self.sample_count += 1;
-        self.time = self.sample_count as f32 / self.sample_rate as f32;
+        self.time = self.sample_count as f64 / self.sample_rate as f64;
 
         (lip_output + nose_output) as f32
     }
@@ -234,47 +707,56 @@ impl Tract {
                 self.transients.remove(i);
                 continue;
             }
-            let amplitude = trans.strength * 2.0_f64.powf(-trans.exponent * time_alive as f64);
+            let amplitude = trans.strength * 2.0_f64.powf(-trans.exponent * time_alive);
 
             self.right[trans.position] += amplitude * 0.5;
             self.left[trans.position] += amplitude * 0.5;
         }
     }
 
-    fn add_turbulence_noise(&mut self) {
-        const FRICATIVE_ATTACK_TIME: f32 = 0.1; // seconds
+    /// Current envelope of the noise of each turbulence point, 0..1,
+    /// in the order of `turbulence_points`.
+    pub fn turbulence_intensities(&self) -> Vec<f32> {
+        self.turbulence_points
+            .iter()
+            .map(|p| self.turbulence_intensity(p) as f32)
+            .collect()
+    }
 
-        let mut turbulence_noises = Vec::<(f64, f64, f64)>::new();
+    /// Attack or release envelope of the noise of `p` at the current time.
+    fn turbulence_intensity(&self, p: &TurbulencePoint) -> f64 {
+        if f64::is_nan(p.end_time) {
+            (self.time - p.start_time) / self.fricative_attack_time as f64
+        } else {
+            1.0 - (self.time - p.end_time) / self.fricative_release_time as f64
+        }
+        .clamp(0.0, 1.0)
+    }
 
-        for p in &self.turbulence_points {
+    fn add_turbulence_noise(&mut self) {
+        // by index, as a list of the noises to add would allocate every step
+        for i in 0..self.turbulence_points.len() {
+            let p = &self.turbulence_points[i];
             if p.position < 2.0 || p.position > N as f32 {
                 continue;
             }
             if p.diameter <= 0.0 {
                 continue;
             }
+            let (position, diameter) = (p.position as f64, p.diameter as f64);
 
-            let intensity = if f32::is_nan(p.end_time) {
-                (self.time - p.start_time) / FRICATIVE_ATTACK_TIME
-            } else {
-                1.0 - (self.time - p.end_time) / FRICATIVE_ATTACK_TIME
-            }
-            .clamp(0.0, 1.0);
+            let intensity = self.turbulence_intensity(p);
 
             if intensity <= 0.0 {
                 continue;
             }
 
             let turbulence_noise = 0.66
-                * (self.frication_noise_source)()
-                * intensity as f64
+                * self.frication_noise_source.noise()
+                * intensity
                 * self.glottis.get_noise_modulator() as f64;
 
-            turbulence_noises.push((turbulence_noise, p.position as f64, p.diameter as f64));
-        }
-
-        for (noise, pos, diameter) in turbulence_noises.into_iter() {
-            self.add_turbulence_noise_at_position(noise, pos, diameter);
+            self.add_turbulence_noise_at_position(turbulence_noise, position, diameter);
         }
     }
 
@@ -284,21 +766,17 @@ impl Tract {
         position: f64,
         diameter: f64,
     ) {
-        let i = position.floor() as i64;
-        let delta = position - i as f64;
         let thinnes0 = (8.0 * (0.7 - diameter)).clamp(0.0, 1.0);
         let openness = (30.0 * (diameter - 0.3)).clamp(0.0, 1.0);
-        let noise0 = turbulence_noise * (1.0 - delta) * thinnes0 * openness;
-        let noise1 = turbulence_noise * delta * thinnes0 * openness;
-        if i + 1 < N as i64 {
-            let idx = (i + 1) as usize;
-            self.right[idx] += noise0 * 0.5;
-            self.left[idx] += noise0 * 0.5;
-        }
-        if i + 2 < N as i64 {
-            let idx = (i + 2) as usize;
-            self.right[idx] += noise1 * 0.5;
-            self.left[idx] += noise1 * 0.5;
+        let (first, weights) = self.turbulence_distribution.weights(position);
+        for (k, weight) in weights.iter().enumerate() {
+            let idx = first + k as i64;
+            if *weight == 0.0 || idx < 0 || idx >= N as i64 {
+                continue;
+            }
+            let noise = turbulence_noise * weight * thinnes0 * openness;
+            self.right[idx as usize] += noise * 0.5;
+            self.left[idx as usize] += noise * 0.5;
         }
     }
 }
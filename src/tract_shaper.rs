@@ -1,7 +1,31 @@
 use std::f64::consts::PI;
 
-use crate::{math, tract::Tract, transient::Transient};
+use crate::{
+    config::MorphCurve, constriction::Constriction, math, tract::Tract, transient::Transient,
+};
 
+/// A scheduled closure and release of the tract, in tract time (seconds).
+#[derive(Clone)]
+struct Plosive {
+    position: usize,
+    release_start: f64,
+    release_end: f64,
+}
+
+/// A scheduled interpolation between two rest shapes, in tract time (seconds).
+#[derive(Clone)]
+struct Morph {
+    from: [f64; Tract::N],
+    to: [f64; Tract::N],
+    from_tongue: (f64, f64),
+    to_tongue: (f64, f64),
+    start: f64,
+    end: f64,
+}
+
+/// Moves the `tract` diameters towards the shape given by the tongue position
+/// and the velum, and triggers transients when obstructions open.
+#[derive(Clone)]
 pub struct TractShaper {
     pub tract: Tract,
     velum_open_target: f32,
@@ -10,11 +34,33 @@ pub struct TractShaper {
     velum_target: f32,
     pub tongue_index: f64,
     pub tongue_diameter: f64,
+    /// how fast tract diameters move towards their targets
+    pub movement_speed: f64,
+    /// relative opening speed of the cells behind the nasal branch
+    pub body_speed: f64,
+    /// relative opening speed of the cells from the tongue tip on
+    pub tip_speed: f64,
+    /// when true, diameters and the velum jump to their targets instantly
+    pub frozen: bool,
+    /// overrides `movement_speed` until the current glide completes
+    glide_speed: Option<f64>,
+    plosive: Option<Plosive>,
+    /// held narrowing of the tract, on top of the tongue position
+    pub constriction: Option<Constriction>,
+    /// rest diameters overriding the tongue position, if set
+    area_function: Option<[f64; Tract::N]>,
+    morph: Option<Morph>,
+    pub morph_curve: MorphCurve,
     last_obstruction: i32,
 }
 
 const GRID_OFFSET: f64 = 1.7;
 const MOVEMENT_SPEED: f64 = 15.0;
+/// diameters move down this many times faster than up
+const CLOSING_SPEED: f64 = 2.0;
+/// the tongue body opens slower than the tip
+const BODY_SPEED: f64 = 0.6;
+const TIP_SPEED: f64 = 1.0;
 
 impl TractShaper {
     pub fn new(tract: Tract) -> TractShaper {
@@ -25,6 +71,16 @@ impl TractShaper {
             velum_target: 0.0,
             tongue_index: 12.9,
             tongue_diameter: 2.43,
+            movement_speed: MOVEMENT_SPEED,
+            body_speed: BODY_SPEED,
+            tip_speed: TIP_SPEED,
+            frozen: false,
+            glide_speed: None,
+            plosive: None,
+            constriction: None,
+            area_function: None,
+            morph: None,
+            morph_curve: MorphCurve::default(),
             last_obstruction: -1,
             target_diameter: [0.0; Tract::N],
         };
@@ -43,8 +99,195 @@ impl TractShaper {
         }
     }
 
+    fn update_target_diameters(&mut self) {
+        self.update_morph();
+        for i in 0..Tract::N {
+            self.target_diameter[i] = self.target_rest_diameter(i);
+        }
+        if let Some(constriction) = self.constriction {
+            constriction.apply(&mut self.target_diameter);
+        }
+        if let Some(constriction) = self.plosive_constriction() {
+            constriction.apply(&mut self.target_diameter);
+        }
+    }
+
+    /// Closes the tract completely at `position` for `closure` seconds,
+    /// then reopens it over `release` seconds. If the velum is closed,
+    /// opening the obstruction produces a burst transient.
+    pub fn plosive(&mut self, position: usize, closure: f64, release: f64) {
+        let now = self.tract.time;
+        self.plosive = Some(Plosive {
+            position: position.clamp(2, Tract::N - 1),
+            release_start: now + closure.max(0.0),
+            release_end: now + closure.max(0.0) + release.max(0.0),
+        });
+    }
+
+    fn plosive_constriction(&mut self) -> Option<Constriction> {
+        let plosive = self.plosive.as_ref()?;
+        let now = self.tract.time;
+        let rest_diameter = self.target_rest_diameter(plosive.position);
+        let diameter = if now < plosive.release_start {
+            0.0
+        } else if now < plosive.release_end {
+            rest_diameter * (now - plosive.release_start)
+                / (plosive.release_end - plosive.release_start)
+        } else {
+            self.plosive = None;
+            return None;
+        };
+        Some(Constriction {
+            index: plosive.position as f64,
+            diameter,
+        })
+    }
+
+    /// Moves the tongue to the specified position, adjusting movement speed
+    /// so that the tract reaches its new shape in about `duration` seconds.
+    /// Regular `movement_speed` is restored once the glide completes.
+    pub fn glide_tongue(&mut self, tongue_index: f64, tongue_diameter: f64, duration: f64) {
+        self.tongue_index = tongue_index;
+        self.tongue_diameter = tongue_diameter;
+        self.update_target_diameters();
+
+        let mut speed: f64 = 0.0;
+        for i in 0..Tract::N {
+            let distance = self.target_diameter[i] - self.tract.diameter[i];
+            let rate = if distance > 0.0 {
+                self.slow_return(i)
+            } else {
+                CLOSING_SPEED
+            };
+            speed = speed.max(distance.abs() / rate);
+        }
+        self.glide_speed = if speed > 0.0 {
+            Some(speed / duration.max(f64::EPSILON))
+        } else {
+            None
+        };
+    }
+
+    /// Opening speed of cell `i` relative to `movement_speed`,
+    /// ramping from `body_speed` at the nasal branch to `tip_speed`.
+    fn slow_return(&self, i: usize) -> f64 {
+        if i < Tract::NOSE_START {
+            self.body_speed
+        } else if i >= Tract::TIP_START {
+            self.tip_speed
+        } else {
+            self.body_speed
+                + (self.tip_speed - self.body_speed) * (i - Tract::NOSE_START) as f64
+                    / (Tract::TIP_START - Tract::NOSE_START) as f64
+        }
+    }
+
+    /// Resamples `areas`, running from the glottis to the lips, onto the
+    /// tract cells with linear interpolation, and uses the resulting
+    /// diameters as the rest shape instead of the tongue position.
+    /// `None` goes back to the tongue position.
+    pub fn set_area_function(&mut self, areas: Option<&[f64]>) {
+        self.area_function = areas.map(|areas| {
+            if areas.is_empty() {
+                panic!("area function must not be empty");
+            }
+            let mut diameters = [0.0; Tract::N];
+            for (i, diameter) in diameters.iter_mut().enumerate() {
+                // cell centers of both resolutions line up
+                let x = ((i as f64 + 0.5) * areas.len() as f64 / Tract::N as f64 - 0.5)
+                    .clamp(0.0, (areas.len() - 1) as f64);
+                let k = (x.floor() as usize).min(areas.len() - 1);
+                let next = (k + 1).min(areas.len() - 1);
+                let area = math::interpolate(areas[k], areas[next], x - k as f64);
+                *diameter = area.max(0.0).sqrt();
+            }
+            diameters
+        });
+    }
+
+    fn target_rest_diameter(&self, i: usize) -> f64 {
+        if let Some(morph) = &self.morph {
+            let weight = self.morph_weight(morph);
+            return math::interpolate(morph.from[i], morph.to[i], weight);
+        }
+        match &self.area_function {
+            Some(diameters) => diameters[i],
+            None => self.get_rest_diameter(i),
+        }
+    }
+
+    /// Area function currently set, as cell areas.
+    pub fn area_function(&self) -> Option<Vec<f64>> {
+        self.area_function
+            .map(|diameters| diameters.iter().map(|d| d * d).collect())
+    }
+
+    /// Moves the rest shape to the one given by the tongue position and
+    /// `areas` (see `set_area_function`) over `duration` seconds,
+    /// following `morph_curve`. Starts from the current rest shape,
+    /// even in the middle of another morph.
+    pub fn morph_to(
+        &mut self,
+        tongue_index: f64,
+        tongue_diameter: f64,
+        areas: Option<&[f64]>,
+        duration: f64,
+    ) {
+        let mut from = [0.0; Tract::N];
+        for (i, d) in from.iter_mut().enumerate() {
+            *d = self.target_rest_diameter(i);
+        }
+        let from_tongue = (self.tongue_index, self.tongue_diameter);
+
+        self.morph = None;
+        self.set_area_function(areas);
+        self.tongue_index = tongue_index;
+        self.tongue_diameter = tongue_diameter;
+        let mut to = [0.0; Tract::N];
+        for (i, d) in to.iter_mut().enumerate() {
+            *d = self.target_rest_diameter(i);
+        }
+
+        let start = self.tract.time;
+        self.morph = Some(Morph {
+            from,
+            to,
+            from_tongue,
+            to_tongue: (tongue_index, tongue_diameter),
+            start,
+            end: start + duration.max(0.0),
+        });
+        self.update_morph();
+    }
+
+    fn morph_weight(&self, morph: &Morph) -> f64 {
+        let t = if morph.end > morph.start {
+            ((self.tract.time - morph.start) / (morph.end - morph.start)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.morph_curve.weight(t)
+    }
+
+    /// Moves the tongue along with the morph, ending it when complete.
+    fn update_morph(&mut self) {
+        let morph = match &self.morph {
+            Some(morph) => morph,
+            None => return,
+        };
+        let (from, to) = (morph.from_tongue, morph.to_tongue);
+        if self.tract.time >= morph.end {
+            (self.tongue_index, self.tongue_diameter) = to;
+            self.morph = None;
+            return;
+        }
+        let weight = self.morph_weight(morph);
+        self.tongue_index = math::interpolate(from.0, to.0, weight);
+        self.tongue_diameter = math::interpolate(from.1, to.1, weight);
+    }
+
     pub fn get_rest_diameter(&self, i: usize) -> f64 {
-        if i < 7 {
+        if i < Tract::GLOTTIS_END {
             return 0.6;
         }
         if i < Tract::BLADE_START {
@@ -69,30 +312,43 @@ impl TractShaper {
         1.5 - curve
     }
 
+    /// Moves the tract to its target shape at once, and freezes
+    /// the reflection coefficients at the values for that shape.
+    pub fn snap_to_target(&mut self) {
+        self.update_target_diameters();
+        self.tract.diameter = self.target_diameter;
+        for _ in 0..2 {
+            self.tract.calculate_new_block_parameters(f64::INFINITY);
+        }
+    }
+
     pub fn adjust_tract_shape(&mut self, delta_time: f64) {
-        let amount = delta_time * MOVEMENT_SPEED;
+        self.update_target_diameters();
+        let amount = delta_time * self.movement_speed;
+        let tract_amount = delta_time * self.glide_speed.unwrap_or(self.movement_speed);
         let mut new_last_obstruction: i32 = -1;
+        let mut settled = true;
         for i in 0..Tract::N {
             let diameter = self.tract.diameter[i];
             let target_diameter = self.target_diameter[i];
             if diameter <= 0.0 {
                 new_last_obstruction = i as i32;
             }
-            let slow_return = if i < Tract::NOSE_START {
-                0.6
-            } else if i >= Tract::TIP_START {
-                1.0
+
+            self.tract.diameter[i] = if self.frozen {
+                target_diameter
             } else {
-                0.6 + 0.4 * (i - Tract::NOSE_START) as f64
-                    / (Tract::TIP_START - Tract::NOSE_START) as f64
+                math::move_towards(
+                    diameter,
+                    target_diameter,
+                    self.slow_return(i) * tract_amount,
+                    CLOSING_SPEED * tract_amount,
+                )
             };
-
-            self.tract.diameter[i] = math::move_towards(
-                diameter,
-                target_diameter,
-                slow_return * amount,
-                2.0 * amount,
-            );
+            settled &= self.tract.diameter[i] == target_diameter;
+        }
+        if settled {
+            self.glide_speed = None;
         }
 
         if self.last_obstruction >= 0
@@ -102,12 +358,16 @@ impl TractShaper {
             self.add_transient(self.last_obstruction as usize);
         }
         self.last_obstruction = new_last_obstruction;
-        self.tract.nose_diameter[0] = math::move_towards(
-            self.tract.nose_diameter[0],
-            self.velum_target as f64,
-            amount * 0.25,
-            amount * 0.1,
-        );
+        self.tract.nose_diameter[0] = if self.frozen {
+            self.velum_target as f64
+        } else {
+            math::move_towards(
+                self.tract.nose_diameter[0],
+                self.velum_target as f64,
+                amount * 0.25,
+                amount * 0.1,
+            )
+        };
     }
 
     fn add_transient(&mut self, position: usize) {
@@ -122,19 +382,68 @@ impl TractShaper {
 
     fn shape_noise(&mut self, velum_open: bool) {
         self.set_velum_open(velum_open);
-        for i in 0..Tract::NOSE_LEN {
-            let d = i as f64 * 2.0 / Tract::NOSE_LEN as f64;
-            let mut diameter = if i == 0 {
+        let nose_len = self.tract.nose_len();
+        for i in 0..nose_len {
+            self.tract.nose_diameter[i] = if i == 0 {
                 self.velum_target as f64
-            } else if d < 1.0 {
-                0.4 + 1.6 * d
             } else {
-                0.5 + 1.5 * (2.0 - d)
+                Self::default_nose_diameter(i, nose_len)
             };
+        }
+    }
+
+    fn default_nose_diameter(i: usize, nose_len: usize) -> f64 {
+        let d = i as f64 * 2.0 / nose_len as f64;
+        let diameter = if d < 1.0 {
+            0.4 + 1.6 * d
+        } else {
+            0.5 + 1.5 * (2.0 - d)
+        };
+        diameter.min(1.9)
+    }
 
-            diameter = diameter.min(1.9);
-            self.tract.nose_diameter[i] = diameter;
+    /// Replaces nasal tract diameters with `profile`, whose length becomes
+    /// the new nasal tract length. The first cell is the velum, which keeps
+    /// following `set_velum_open`, so `profile[0]` is ignored.
+    /// Changing the length silences the nasal tract.
+    pub fn set_nose_profile(&mut self, profile: &[f64]) {
+        let velum = self.tract.nose_diameter[0];
+        if profile.len() != self.tract.nose_len() {
+            self.tract.set_nose_len(profile.len());
         }
+        self.tract.nose_diameter[1..].copy_from_slice(&profile[1..]);
+        self.tract.nose_diameter[0] = velum;
+        self.calculate_nose_reflections();
+    }
+
+    /// Changes the nasal tract length, restoring the default diameter profile.
+    /// Silences the nasal tract.
+    pub fn set_nose_len(&mut self, nose_len: usize) {
+        let velum = self.tract.nose_diameter[0];
+        self.tract.set_nose_len(nose_len);
+        for i in 1..nose_len {
+            self.tract.nose_diameter[i] = Self::default_nose_diameter(i, nose_len);
+        }
+        self.tract.nose_diameter[0] = velum;
+        self.calculate_nose_reflections();
+    }
+
+    /// nose reflections are calculated with open velum
+    fn calculate_nose_reflections(&mut self) {
+        let velum = self.tract.nose_diameter[0];
+        self.tract.nose_diameter[0] = self.velum_open_target as f64;
+        self.tract.calculate_nose_reflections();
+        self.tract.nose_diameter[0] = velum;
+    }
+
+    /// Whether the velum is being opened, see `set_velum_open`.
+    pub fn velum_open(&self) -> bool {
+        self.velum_target == self.velum_open_target
+    }
+
+    /// Velum opening (nasal tract entrance diameter) being moved towards.
+    pub fn velum_target(&self) -> f64 {
+        self.velum_target as f64
     }
 
     pub fn set_velum_open(&mut self, velum_open: bool) {
@@ -1,7 +1,15 @@
+/// Exponentially decaying click injected into the tract,
+/// e.g. when a plosive closure opens.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transient {
+    /// tract cell the click is injected at
     pub position: usize,
-    pub start_time: f32,
-    pub life_time: f32,
+    /// tract time the transient started at, in seconds
+    pub start_time: f64,
+    /// how long the transient lasts, in seconds
+    pub life_time: f64,
+    /// initial amplitude
     pub strength: f64,
+    /// decay rate, in halvings per second
     pub exponent: f64,
-}
\ No newline at end of file
+}
@@ -1,108 +1,1148 @@
-use std::time::Duration;
+use std::{sync::Mutex, time::Duration};
 
 use crate::{
-    glottis::Glottis, noise::NoiseSource, tract::Tract, tract_shaper::TractShaper,
-    turbulence::TurbulencePoint,
+    agc::{Agc, AgcSettings},
+    config::{MorphCurve, PinkTromboneConfig},
+    constriction::Constriction,
+    dc_blocker::DcBlocker,
+    decimation::{DecimationMode, Decimator},
+    envelope::EnvelopeFollower,
+    formants,
+    gesture::Gesture,
+    glottal_source::GlottalSource,
+    glottis::{Glottis, LoudnessMode, MIN_FREQUENCY},
+    math,
+    noise::{Filter, LiveNoise, NoiseMode, NoiseSource, NoiseStream},
+    params::Params,
+    pcm,
+    phoneme::Phoneme,
+    pitch,
+    sink::AudioSink,
+    tilt::SpectralTilt,
+    tract::{NoseJunction, Tract},
+    tract_shaper::TractShaper,
+    transient::Transient,
+    turbulence::{TurbulenceDistribution, TurbulencePoint},
+    unison::Unison,
 };
 
+/// Error returned by `PinkTrombone::synthesize_checked` in debug builds
+/// when the output contains NaN or infinity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NonFinite {
+    /// Index of the first non-finite sample in the buffer.
+    pub index: usize,
+}
+
+impl std::fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "non-finite output at sample {}", self.index)
+    }
+}
+
+impl std::error::Error for NonFinite {}
+
+/// Error returned when buffers that must have the same length do not.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    /// Length of the output buffer.
+    pub expected: usize,
+    /// Length of the other buffer.
+    pub actual: usize,
+}
+
+impl std::fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "buffer length {} does not match output length {}",
+            self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for LengthMismatch {}
+
+/// Signal driving the tract.
+#[derive(Copy, Clone)]
+enum Excitation<'a> {
+    Glottis,
+    /// the main glottis together with the unison voices
+    Unison,
+    /// external glottal source
+    Source(&'a [f32]),
+}
+
+/// Output of a single tract step.
+#[derive(Copy, Clone, Default)]
+struct TractSample {
+    vocal: f32,
+    lip: f32,
+    nose: f32,
+}
+
+impl TractSample {
+    fn interpolate(a: TractSample, b: TractSample, t: f32) -> TractSample {
+        TractSample {
+            vocal: math::interpolate(a.vocal, b.vocal, t),
+            lip: math::interpolate(a.lip, b.lip, t),
+            nose: math::interpolate(a.nose, b.nose, t),
+        }
+    }
+}
+
+/// Linear interpolation from the tract sample rate to twice the output
+/// sample rate, used when the sound speed is changed.
+#[derive(Copy, Clone)]
+struct TractResampler {
+    /// position of the next output between `previous` (0) and `current` (1)
+    position: f64,
+    previous: TractSample,
+    current: TractSample,
+}
+
+/// Linear fade-in over the first output samples after construction
+/// or `reset`.
+#[derive(Copy, Clone)]
+struct StartupFade {
+    len: usize,
+    /// output samples synthesized since construction or `reset`, saturating
+    position: usize,
+}
+
+impl StartupFade {
+    /// Gain of the `i`th sample of the current block.
+    fn gain(&self, i: usize) -> f32 {
+        let n = self.position.saturating_add(i);
+        if n >= self.len {
+            1.0
+        } else {
+            n as f32 / self.len as f32
+        }
+    }
+}
+
+/// Pink Trombone speech synthesizer.
+///
+/// `PinkTrombone` is both `Send` and `Sync`: it owns all of its state and
+/// has no interior mutability. Synthesis and setters take `&mut self`, so
+/// sharing between threads requires external synchronization
+/// (e.g. `Arc<Mutex<PinkTrombone>>`), while read-only accessors can be
+/// called through a shared `&PinkTrombone` (e.g. behind an `Arc` or `RwLock`).
+///
+/// For real-time use, queue parameter changes with `set_params_pending`
+/// and pull audio with `fill`, keeping any lock held only briefly.
+/// `fill`, `synthesize` and its variants writing into caller-provided
+/// buffers, `set_params_pending` and the plain setters do not allocate,
+/// except that a closing consonant may add a transient to a short list.
+/// Methods returning a `Vec`, as well as `set_nose_profile`,
+/// `set_nose_length`, `set_track_glottal_closures` and `calibrate_gain`,
+/// allocate.
+///
+/// Cloning copies the entire synthesizer state, including the positions
+/// of the noise sources, so a clone continues with identical output.
+/// The RNG kept by `with_owned_rng` is not copied: the clone gets its own
+/// generator, seeded from the original's state without drawing from its
+/// RNG, so the clone regenerates different noise than the original,
+/// while the original is left unchanged.
+#[derive(Clone)]
 pub struct PinkTrombone {
     shaper: TractShaper,
     sample_rate: u32,
+    block_len: usize,
+    decimator: Decimator,
+    spectral_tilt: SpectralTilt,
+    pending: Params,
+    /// `None` unless glottal closure tracking is enabled
+    glottal_closures: Option<Vec<usize>>,
+    /// output samples synthesized since the start of the last synthesize call
+    call_offset: usize,
+    startup_fade: StartupFade,
+    unison: Unison,
+    agc: Option<Agc>,
+    dc_blocker: Option<DcBlocker>,
+    envelope: EnvelopeFollower,
+    /// last sample before pre-emphasis, see `synthesize_preemphasis`
+    preemphasis_last: f32,
+    /// time passed to `advance` not yet synthesized,
+    /// in nanoseconds times the sample rate
+    advance_remainder: u128,
+    /// block synthesized ahead by `next_sample`,
+    /// and the position of its next sample
+    sample_buffer: Vec<f32>,
+    sample_buffer_pos: usize,
+    /// tongue positions for `step_to_grid`
+    vowel_grid: Vec<(f64, f64)>,
+    output_gain: f32,
+    glottal_source_enabled: bool,
+    sound_speed: f32,
+    tract_resampler: TractResampler,
+    /// RNG passed to `with_owned_rng`
+    owned_rng: Option<OwnedRng>,
+}
+
+/// RNG kept by `with_owned_rng`, behind a `Mutex` to keep `PinkTrombone`
+/// `Sync`.
+struct OwnedRng {
+    rng: Mutex<Box<dyn NoiseSource<f64> + Send>>,
+    /// seed of the RNG of the next clone
+    fork_seed: u64,
+}
+
+impl OwnedRng {
+    fn new(rng: Box<dyn NoiseSource<f64> + Send>, seed: u16) -> OwnedRng {
+        OwnedRng {
+            rng: Mutex::new(rng),
+            fork_seed: OwnedRng::next_seed(seed.into()),
+        }
+    }
+
+    fn next_seed(seed: u64) -> u64 {
+        (LiveNoise::with_seed(seed).noise() * (1u64 << 53) as f64) as u64
+    }
+
+    fn get_mut(&mut self) -> &mut (dyn NoiseSource<f64> + Send) {
+        let rng = self.rng.get_mut().unwrap_or_else(|e| e.into_inner());
+        rng.as_mut()
+    }
+
+    /// Draws new noise from the RNG for `trombone`.
+    fn regenerate(&mut self, trombone: &mut PinkTrombone, mode: NoiseMode) {
+        trombone.set_noise_mode(mode, self.get_mut());
+        // so that clones made from here on get a different RNG
+        self.fork_seed = OwnedRng::next_seed(self.fork_seed);
+    }
 }
 
+impl Clone for OwnedRng {
+    fn clone(&self) -> OwnedRng {
+        OwnedRng {
+            rng: Mutex::new(Box::new(LiveNoise::with_seed(self.fork_seed))),
+            fork_seed: OwnedRng::next_seed(!self.fork_seed),
+        }
+    }
+}
+
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    assert_send::<PinkTrombone>();
+    assert_sync::<PinkTrombone>();
+};
+
 impl PinkTrombone {
+    /// Creates a synthesizer at `sample_rate`. `seed` drives the random
+    /// pitch and tenseness drift; a single draw from `rng` seeds the
+    /// aspiration and frication noise, each from its own independent stream,
+    /// so the two textures are decorrelated. To feed the noise from
+    /// sources of your own instead, use `with_noise_sources`.
     pub fn new(sample_rate: u32, rng: &mut dyn NoiseSource<f64>, seed: u16) -> PinkTrombone {
+        PinkTrombone::with_noise_mode(sample_rate, rng, seed, NoiseMode::default())
+    }
+
+    /// Like `new`, but keeps `rng` to draw new noise from later with
+    /// `regenerate_noise`, without the caller holding on to it.
+    pub fn with_owned_rng(
+        sample_rate: u32,
+        mut rng: Box<dyn NoiseSource<f64> + Send>,
+        seed: u16,
+    ) -> PinkTrombone {
+        let mut trombone = PinkTrombone::new(sample_rate, rng.as_mut(), seed);
+        trombone.owned_rng = Some(OwnedRng::new(rng, seed));
+        trombone
+    }
+
+    /// Like `new`, but with aspiration and frication noise looping every
+    /// `loop_size` samples (see `set_noise_loop_size`).
+    pub fn with_noise_loop_size(
+        sample_rate: u32,
+        rng: &mut dyn NoiseSource<f64>,
+        seed: u16,
+        loop_size: usize,
+    ) -> PinkTrombone {
+        PinkTrombone::with_noise_mode(sample_rate, rng, seed, NoiseMode::Looped(loop_size))
+    }
+
+    /// Like `new`, but with aspiration and frication noise generated
+    /// according to `mode` (see `set_noise_mode`).
+    pub fn with_noise_mode(
+        sample_rate: u32,
+        rng: &mut dyn NoiseSource<f64>,
+        seed: u16,
+        mode: NoiseMode,
+    ) -> PinkTrombone {
+        let (mut aspiration_rng, mut frication_rng) = NoiseStream::split(rng);
+        PinkTrombone::with_noise_sources(
+            sample_rate,
+            &mut aspiration_rng,
+            &mut frication_rng,
+            seed,
+            mode,
+        )
+    }
+
+    /// Like `with_noise_mode`, but fills the aspiration noise from
+    /// `aspiration_rng` and the frication noise from `frication_rng`
+    /// directly, rather than from streams seeded by a single draw.
+    /// Use to control the noise exactly, e.g. `testing::ConstantNoise(0.5)`
+    /// for both silences it. `NoiseMode::Live` still only takes a seed
+    /// from each of them.
+    pub fn with_noise_sources(
+        sample_rate: u32,
+        aspiration_rng: &mut dyn NoiseSource<f64>,
+        frication_rng: &mut dyn NoiseSource<f64>,
+        seed: u16,
+        mode: NoiseMode,
+    ) -> PinkTrombone {
         if sample_rate >= u32::MAX / 2 {
             panic!("sample_rate too large");
         };
         if sample_rate == 0 {
             panic!("sample_rate must not be 0");
         }
-        let glottis = Glottis::new(sample_rate, rng, seed);
+        let glottis = Glottis::with_noise_mode(sample_rate, aspiration_rng, seed, mode);
         // tract runs at twice the sample rate
-        let tract = Tract::new(glottis, 2 * sample_rate, rng);
+        let tract = Tract::with_noise_mode(glottis, 2 * sample_rate, frication_rng, mode);
         PinkTrombone {
             sample_rate,
             shaper: TractShaper::new(tract),
+            block_len: PinkTrombone::DEFAULT_BLOCK_LEN,
+            decimator: Decimator::default(),
+            spectral_tilt: SpectralTilt::new(),
+            pending: Params::default(),
+            glottal_closures: None,
+            call_offset: 0,
+            startup_fade: StartupFade {
+                len: 0,
+                position: 0,
+            },
+            unison: Unison::new(),
+            agc: None,
+            dc_blocker: None,
+            envelope: EnvelopeFollower::new(PinkTrombone::DEFAULT_ENVELOPE_SMOOTHING, sample_rate),
+            preemphasis_last: 0.0,
+            advance_remainder: 0,
+            sample_buffer: Vec::new(),
+            sample_buffer_pos: 0,
+            vowel_grid: Vec::new(),
+            output_gain: 1.0,
+            glottal_source_enabled: true,
+            sound_speed: PinkTrombone::SOUND_SPEED,
+            tract_resampler: TractResampler {
+                position: 1.0,
+                previous: TractSample::default(),
+                current: TractSample::default(),
+            },
+            owned_rng: None,
         }
     }
 
-    const MAX_BLOCK_LEN: usize = 512;
+    const DEFAULT_BLOCK_LEN: usize = 512;
+    const MIN_BLOCK_LEN: usize = 16;
+
+    /// tongue range covered by `set_tongue_vowel`
+    const FRONT_INDEX: f64 = (Tract::TIP_START - 3) as f64;
+    const BACK_INDEX: f64 = (Tract::BLADE_START + 2) as f64;
+    const HIGH_DIAMETER: f64 = 2.05;
+    const LOW_DIAMETER: f64 = 3.5;
+
+    /// speed of sound in air at 20°C, in m/s
+    const SOUND_SPEED: f32 = 343.0;
+    const DEFAULT_ENVELOPE_SMOOTHING: Duration = Duration::from_millis(20);
 
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
 
+    /// Sample rate the vocal tract waveguide runs at.
+    /// At the default sound speed the tract is stepped twice per output
+    /// sample, so this is `2 * sample_rate()`, and tract-domain frequencies
+    /// (e.g. the tract's Nyquist limit) should be computed against it.
+    /// `set_sound_speed` scales it.
+    pub fn tract_sample_rate(&self) -> u32 {
+        self.tract().sample_rate()
+    }
+
+    /// Physical length of one tract cell, in cm. A wave travels one cell per
+    /// tract sample, so with sound travelling 343 m/s (air at 20°C, see
+    /// `set_sound_speed`), a cell is `343 m/s / tract_sample_rate()` long.
+    /// Since the cell count is fixed, the tract gets physically longer
+    /// at lower sample rates.
+    pub fn cell_length_cm(&self) -> f32 {
+        100.0 * self.sound_speed / self.tract_sample_rate() as f32
+    }
+
+    /// Speed of sound in the tract, in m/s.
+    pub fn sound_speed(&self) -> f32 {
+        self.sound_speed
+    }
+    /// Sets the speed of sound in the tract, keeping its physical length.
+    /// Sound crossing the same tract faster raises all resonances in
+    /// proportion, as in helium speech (about 970 m/s, formants 2.8 times
+    /// higher); a slower speed, as in heavy gases, lowers them.
+    /// The glottal pitch is not affected. Defaults to 343 m/s (air at 20°C).
+    ///
+    /// The waveguide delay per cell is one tract sample, so this is done by
+    /// running the tract at a proportionally higher or lower rate and
+    /// linearly interpolating its output to `2 * sample_rate()`. CPU cost
+    /// grows with the speed, and at speeds above the default the
+    /// interpolation lets some aliasing through.
+    /// Speeds are limited to those giving a tract rate of at least 1 Hz
+    /// and below `u32::MAX` Hz.
+    pub fn set_sound_speed(&mut self, m_per_s: f32) {
+        let speed_per_hz = PinkTrombone::SOUND_SPEED as f64 / (2.0 * self.sample_rate as f64);
+        let min = (speed_per_hz as f32).max(f32::MIN_POSITIVE);
+        let max = (speed_per_hz * (u32::MAX - 1) as f64) as f32;
+        let m_per_s = math::clamp_or(m_per_s, min, max, self.sound_speed);
+        let tract_sample_rate = (m_per_s as f64 / speed_per_hz).round();
+        self.sound_speed = m_per_s;
+        self.tract_mut()
+            .set_sample_rate((tract_sample_rate as u32).max(1));
+    }
+
+    /// Physical length of the oral tract from the glottis to the lips, in cm,
+    /// see `cell_length_cm`. About 15.7 cm at a 48kHz sample rate.
+    pub fn tract_length_cm(&self) -> f32 {
+        Tract::N as f32 * self.cell_length_cm()
+    }
+
+    /// Lowest resonance of an idealized uniform tube as long as the tract,
+    /// closed at the glottis and open at the lips: the quarter-wavelength
+    /// frequency `sound_speed / (4 * length)`, in Hz. About 545 Hz at a
+    /// 48kHz sample rate. This is a reference point for `estimate_formants`,
+    /// not the first formant of the current tract, which the tract shape
+    /// moves well away from it (lower for "u", higher for "a").
+    pub fn tube_fundamental(&self) -> f32 {
+        self.sound_speed / (4.0 * self.tract_length_cm() / 100.0)
+    }
+
     /// How much time has already been generated.
     pub fn time(&self) -> Duration {
-        Duration::from_secs_f32(self.tract().time)
+        let sample_rate = self.tract().sample_rate() as u64;
+        let sample_count = self.tract().sample_count();
+        Duration::from_secs(sample_count / sample_rate)
+            + Duration::from_nanos(sample_count % sample_rate * 1_000_000_000 / sample_rate)
     }
 
     // /// -1..+1
     //pub fn noise(self) -> f32 {}
 
-    /// 0..1
+    /// Current (live) voicing intensity, 0..1.
+    /// Unlike the `target_*` parameters, this has no separate target: it ramps
+    /// towards 1 on its own while voicing, over about 80ms, and back to 0
+    /// over about 210ms otherwise, at any sample rate. It can be overridden
+    /// directly.
+    ///
+    /// Like the other numeric setters, `set_intensity` clamps values outside
+    /// of the documented range to it and ignores NaN, keeping the previous
+    /// value, so that bad input can not silence the synthesizer for good.
     pub fn intensity(&self) -> f32 {
-        self.glottis().intensity
+        self.glottis().intensity()
     }
     pub fn set_intensity(&mut self, intensity: f32) {
-        self.glottis_mut().intensity = intensity
+        self.glottis_mut().set_intensity(intensity)
     }
 
-    /// 0..1
+    /// Amplitude of the glottal pulses, 0..1, updated every glottal cycle
+    /// according to `loudness_mode`. Setting it switches to
+    /// `LoudnessMode::Manual`, so that the value persists.
     pub fn loudness(&self) -> f32 {
-        self.glottis().loudness
+        self.glottis().loudness()
     }
     pub fn set_loudness(&mut self, loudness: f32) {
-        self.glottis_mut().loudness = loudness
+        self.glottis_mut().set_loudness(loudness)
     }
 
-    /// 0..
+    /// How `loudness` is chosen: derived from tenseness (the default),
+    /// fixed, or left to `set_loudness`. Switching to `Fixed` applies the
+    /// value immediately, and fixed values are clamped to 0..1; ones that
+    /// are NaN are ignored.
+    pub fn loudness_mode(&self) -> LoudnessMode {
+        self.glottis().loudness_mode()
+    }
+    pub fn set_loudness_mode(&mut self, mode: LoudnessMode) {
+        self.glottis_mut().set_loudness_mode(mode)
+    }
+
+    /// Target fundamental frequency in Hz, 10..
+    /// The actual frequency glides towards it; see `current_frequency`.
     pub fn target_frequency(&self) -> f32 {
-        self.glottis().target_frequency
+        self.glottis().target_frequency()
+    }
+    /// Frequency in Hz used for the current block, after smoothing
+    /// towards `target_frequency` and applying vibrato.
+    pub fn current_frequency(&self) -> f32 {
+        self.glottis().new_frequency
     }
     pub fn set_target_frequency(&mut self, target_frequency: f32) {
-        self.glottis_mut().target_frequency = target_frequency
+        self.glottis_mut().set_target_frequency(target_frequency)
+    }
+
+    /// Upper limit of the glottal frequency in Hz, applied after vibrato.
+    /// Defaults to a quarter of the sample rate, keeping the fundamental
+    /// well below the Nyquist frequency.
+    pub fn max_frequency(&self) -> f32 {
+        self.glottis().max_frequency()
+    }
+    /// Limited to at least the 10 Hz lower bound of `set_target_frequency`.
+    pub fn set_max_frequency(&mut self, max_frequency: f32) {
+        self.glottis_mut().set_max_frequency(max_frequency)
     }
 
-    /// 0..1
+    /// Position within the current glottal cycle, 0..1,
+    /// 0 being the start of the glottal opening.
+    pub fn glottal_phase(&self) -> f32 {
+        self.glottis().phase()
+    }
+    /// Jumps to `phase` of the current glottal cycle, e.g. to align the
+    /// glottal pulses of several synthesizers. Values outside 0..1 wrap,
+    /// and NaN or infinite values are ignored.
+    /// Jumping mid-cycle may cause a small discontinuity in the output.
+    pub fn set_glottal_phase(&mut self, phase: f32) {
+        self.glottis_mut().set_phase(phase)
+    }
+
+    /// Target vocal fold tenseness, 0..1.
+    /// The actual tenseness wanders around it; see `current_tenseness`.
     pub fn target_tenseness(&self) -> f32 {
-        self.glottis().target_tenseness
+        self.glottis().target_tenseness()
+    }
+    /// Tenseness used for the current block, after adding random drift
+    /// and the attack term to `target_tenseness`.
+    pub fn current_tenseness(&self) -> f32 {
+        self.glottis().new_tenseness
     }
     pub fn set_target_tenseness(&mut self, target_tenseness: f32) {
-        self.glottis_mut().target_tenseness = target_tenseness
+        self.glottis_mut().set_target_tenseness(target_tenseness)
     }
 
-    /// 0..44 (see also Tract::n)
+    /// Moves `target_frequency` linearly to `to` Hz over the next `samples`
+    /// output samples, updating it every sample rather than every block,
+    /// for automation without zipper noise. The pitch follows the ramp
+    /// exactly instead of gliding, with vibrato and drift on top.
+    /// 0 samples jumps to `to` at the next sample.
+    ///
+    /// `to` is clamped like in `set_target_frequency`, which cancels the
+    /// ramp. Unison voices only follow the ramp once per block.
+    pub fn ramp_frequency(&mut self, to: f32, samples: usize) {
+        if !to.is_nan() {
+            self.glottis_mut()
+                .ramp_frequency(to.clamp(MIN_FREQUENCY, f32::MAX), samples)
+        }
+    }
+
+    /// Like `ramp_frequency`, for `target_tenseness`.
+    /// Drift is added on top of the ramp.
+    pub fn ramp_tenseness(&mut self, to: f32, samples: usize) {
+        if !to.is_nan() {
+            self.glottis_mut()
+                .ramp_tenseness(to.clamp(0.0, 1.0), samples)
+        }
+    }
+
+    /// Like `ramp_frequency`, for `intensity`. Once the ramp is over,
+    /// intensity ramps up while voicing and fades out otherwise as usual.
+    pub fn ramp_intensity(&mut self, to: f32, samples: usize) {
+        if !to.is_nan() {
+            self.glottis_mut()
+                .ramp_intensity(to.clamp(0.0, 1.0), samples)
+        }
+    }
+
+    /// Sets `target_frequency`, `target_tenseness` and `intensity` at once.
+    /// Like all parameter changes between `synthesize` calls, they take
+    /// effect together at the start of the next block.
+    pub fn set_voice(&mut self, frequency: f32, tenseness: f32, intensity: f32) {
+        self.set_target_frequency(frequency);
+        self.set_target_tenseness(tenseness);
+        self.set_intensity(intensity);
+    }
+
+    /// 0..43 (see also Tract::N)
     pub fn tongue_index(&self) -> f64 {
         self.shaper.tongue_index
     }
     pub fn set_tongue_index(&mut self, tongue_index: f64) {
-        self.shaper.tongue_index = tongue_index
+        self.shaper.tongue_index = math::clamp_or(
+            tongue_index,
+            0.0,
+            (Tract::N - 1) as f64,
+            self.shaper.tongue_index,
+        )
     }
 
-    /// 0..3(?)
+    /// 0..3.5, from a tongue raised to touch the palate to the lowest
+    /// position of the original Pink Trombone.
     pub fn tongue_diameter(&self) -> f64 {
         self.shaper.tongue_diameter
     }
     pub fn set_tongue_diameter(&mut self, tongue_diameter: f64) {
-        self.shaper.tongue_diameter = tongue_diameter
+        self.shaper.tongue_diameter = math::clamp_or(
+            tongue_diameter,
+            0.0,
+            PinkTrombone::LOW_DIAMETER,
+            self.shaper.tongue_diameter,
+        )
+    }
+
+    /// Move the tongue to the position of the specified phoneme.
+    /// The tract then reshapes at the regular articulation speed.
+    pub fn set_phoneme(&mut self, phoneme: Phoneme) {
+        let (index, diameter) = phoneme.tongue();
+        self.set_tongue_index(index);
+        self.set_tongue_diameter(diameter);
+    }
+
+    /// Move the tongue to the position of the specified phoneme,
+    /// temporarily adjusting articulation speed so that the tract
+    /// reaches its new shape in about `duration`.
+    pub fn glide_to_phoneme(&mut self, phoneme: Phoneme, duration: Duration) {
+        let (index, diameter) = phoneme.tongue();
+        self.shaper
+            .glide_tongue(index, diameter, duration.as_secs_f64());
+    }
+
+    /// Tongue positions for `step_to_grid`, as (index, diameter) pairs.
+    /// Empty by default.
+    pub fn vowel_grid(&self) -> &[(f64, f64)] {
+        &self.vowel_grid
+    }
+    /// Positions are clamped to the ranges of `set_tongue_index` and
+    /// `set_tongue_diameter`. Panics if any of them is NaN.
+    pub fn set_vowel_grid(&mut self, positions: Vec<(f64, f64)>) {
+        if positions
+            .iter()
+            .any(|(index, diameter)| index.is_nan() || diameter.is_nan())
+        {
+            panic!("vowel grid positions must not be NaN");
+        }
+        self.vowel_grid = positions
+            .into_iter()
+            .map(|(index, diameter)| {
+                (
+                    index.clamp(0.0, (Tract::N - 1) as f64),
+                    diameter.clamp(0.0, PinkTrombone::LOW_DIAMETER),
+                )
+            })
+            .collect();
+    }
+
+    /// Move the tongue to position `i` of `vowel_grid`, like
+    /// `glide_to_phoneme`, for step sequencing.
+    ///
+    /// The glide overrides `articulation_speed` until the tract settles,
+    /// so the tract takes about `glide` to reach the new shape however
+    /// far it moves, and a zero `glide` snaps to it within a sample.
+    /// Moves started by the regular tongue setters afterwards go at
+    /// `articulation_speed` again, once the glide is done.
+    /// Panics if `i` is not an index of the grid.
+    pub fn step_to_grid(&mut self, i: usize, glide: Duration) {
+        let Some(&(index, diameter)) = self.vowel_grid.get(i) else {
+            panic!(
+                "vowel grid position {} out of range 0..{}",
+                i,
+                self.vowel_grid.len()
+            );
+        };
+        self.shaper
+            .glide_tongue(index, diameter, glide.as_secs_f64());
+    }
+
+    /// Sets the tongue position in vowel quadrilateral coordinates,
+    /// each clamped to 0..1. `front_back` goes from the front (0) to the
+    /// back (1) of the mouth, `high_low` from a raised tongue (0) to a
+    /// lowered one (1), covering the tongue range of the original
+    /// Pink Trombone UI. The corners roughly produce
+    /// "i" as in "see" (0, 0), "u" as in "boot" (1, 0),
+    /// "a" as in "father" (1, 1) and "æ" as in "cat" (0, 1).
+    pub fn set_tongue_vowel(&mut self, front_back: f32, high_low: f32) {
+        let front_back = front_back.clamp(0.0, 1.0) as f64;
+        let high_low = high_low.clamp(0.0, 1.0) as f64;
+        self.set_tongue_index(math::interpolate(
+            PinkTrombone::FRONT_INDEX,
+            PinkTrombone::BACK_INDEX,
+            front_back,
+        ));
+        self.set_tongue_diameter(math::interpolate(
+            PinkTrombone::HIGH_DIAMETER,
+            PinkTrombone::LOW_DIAMETER,
+            high_low,
+        ));
+    }
+    /// Tongue position in the coordinates of `set_tongue_vowel`.
+    /// Positions outside of its range give values outside of 0..1.
+    pub fn tongue_vowel(&self) -> (f32, f32) {
+        let front_back = (self.tongue_index() - PinkTrombone::FRONT_INDEX)
+            / (PinkTrombone::BACK_INDEX - PinkTrombone::FRONT_INDEX);
+        let high_low = (self.tongue_diameter() - PinkTrombone::HIGH_DIAMETER)
+            / (PinkTrombone::LOW_DIAMETER - PinkTrombone::HIGH_DIAMETER);
+        (front_back as f32, high_low as f32)
+    }
+
+    /// Estimates the formant frequencies in Hz of the tract in its current
+    /// shape, in ascending order, from the peaks of its impulse response
+    /// spectrum below 5kHz. The synthesizer state is left unchanged.
+    pub fn estimate_formants(&mut self) -> Vec<f32> {
+        const RESPONSE_LEN: f32 = 0.05; // seconds
+        let len = (RESPONSE_LEN * self.sample_rate as f32) as usize;
+        let response = self.impulse_response(len);
+        formants::estimate(&response, self.sample_rate)
+    }
+
+    /// Magnitude in dB of the transfer function of the oral tract from the
+    /// glottis to the lips, at each of `frequencies` in Hz: the spectral
+    /// envelope the tract in its current shape imposes on the voice.
+    ///
+    /// It is computed directly from the reflection coefficients of the
+    /// junctions between the cells (see `reflection_coefficients`), as a
+    /// lossless tube: an all-pole model with sharper and higher formant
+    /// peaks than the synthesis. The synthesis also damps the waves
+    /// slightly in every cell, couples the nasal tract and the piriform
+    /// fossa, and adds lip radiation and output filtering, none of which
+    /// is included here. 0 dB is a gain of 1 from the glottal source to the
+    /// lip output. Unlike `estimate_formants`, nothing is synthesized.
+    pub fn tract_spectrum(&self, frequencies: &[f32]) -> Vec<f32> {
+        self.tract().spectrum(frequencies)
+    }
+
+    /// Searches for the tongue position (see `set_tongue_vowel`) whose
+    /// first two formants best match `f1` and `f2`, and moves the tongue
+    /// there. Returns the first two formants of the chosen shape.
+    ///
+    /// Runs `iterations` steps of a Nelder-Mead search, starting from the
+    /// current tongue position, with the error measured on a log frequency
+    /// scale. Each step estimates formants once or twice, so this is far
+    /// from real time. About 30 iterations usually converge; more do not
+    /// hurt. The search only finds a local optimum, and the tongue can
+    /// not reach every formant pair: when the targets are unreachable,
+    /// the returned formants are the closest ones found, and may be far off
+    /// (zero if the shape has fewer than two formants below 5kHz).
+    /// Lip rounding and other articulators are not adjusted.
+    pub fn match_formants(&mut self, f1: f32, f2: f32, iterations: usize) -> (f32, f32) {
+        let formants_at = |trombone: &PinkTrombone, vowel: [f64; 2]| {
+            let mut probe = trombone.probe();
+            probe.set_tongue_vowel(vowel[0] as f32, vowel[1] as f32);
+            probe.shaper.snap_to_target();
+            let formants = probe.estimate_formants();
+            match formants[..] {
+                [first, second, ..] => Some((first, second)),
+                _ => None,
+            }
+        };
+        let error = |formants: Option<(f32, f32)>| match formants {
+            Some((first, second)) => {
+                math::sqr((first / f1).ln() as f64) + math::sqr((second / f2).ln() as f64)
+            }
+            None => f64::INFINITY,
+        };
+
+        let (front_back, high_low) = self.tongue_vowel();
+        let best = formants::minimize(
+            |vowel| error(formants_at(self, vowel)),
+            [front_back as f64, high_low as f64],
+            iterations,
+        );
+        self.set_tongue_vowel(best[0] as f32, best[1] as f32);
+        formants_at(self, best).unwrap_or((0.0, 0.0))
+    }
+
+    /// Shapes the tract after an area function, e.g. one measured with MRI,
+    /// instead of the tongue position.
+    ///
+    /// `areas` are cross-sectional areas in cm², running from the glottis to
+    /// the lips, at any spatial resolution: they are linearly resampled onto
+    /// the tract cells, and converted to diameters as `sqrt(area)`,
+    /// the model's own convention. The tract moves towards the new shape at
+    /// the usual articulation speed, and plosives still apply on top of it.
+    /// Tongue setters have no effect until `clear_area_function` is called.
+    /// Panics if `areas` is empty.
+    pub fn set_area_function(&mut self, areas: &[f64]) {
+        self.shaper.set_area_function(Some(areas));
+    }
+    /// Goes back to shaping the tract after the tongue position.
+    pub fn clear_area_function(&mut self) {
+        self.shaper.set_area_function(None);
+    }
+
+    /// The current tract shape settings, to restore or morph to later.
+    pub fn config(&self) -> PinkTromboneConfig {
+        PinkTromboneConfig {
+            tongue_index: self.tongue_index(),
+            tongue_diameter: self.tongue_diameter(),
+            velum_open: self.shaper.velum_open(),
+            area_function: self.shaper.area_function(),
+        }
+    }
+
+    /// Morphs the tract from its current rest shape to the one of `target`
+    /// over `duration`, e.g. for diphthongs. All cell diameters, and the
+    /// reported tongue position, are interpolated along `morph_curve`,
+    /// updated once per block. Unlike moving the tongue, the shape passes
+    /// through no other constrictions on the way. The velum moves to
+    /// `target.velum_open` at its usual speed.
+    ///
+    /// The tract follows the moving shape at `articulation_speed`, so
+    /// morphs much shorter than about 100ms are limited by that speed.
+    /// Tongue and area function setters have no effect until the morph
+    /// completes. Constrictions and plosives still apply on top of it.
+    pub fn morph_to(&mut self, target: &PinkTromboneConfig, duration: Duration) {
+        self.shaper.morph_to(
+            target.tongue_index,
+            target.tongue_diameter,
+            target.area_function.as_deref(),
+            duration.as_secs_f64(),
+        );
+        self.set_velum_open(target.velum_open);
+    }
+
+    /// Interpolation curve used by `morph_to`, `MorphCurve::Linear`
+    /// by default. Changing it affects a morph in progress.
+    pub fn morph_curve(&self) -> MorphCurve {
+        self.shaper.morph_curve
+    }
+    pub fn set_morph_curve(&mut self, curve: MorphCurve) {
+        self.shaper.morph_curve = curve
+    }
+
+    /// Articulates a plosive consonant: closes the tract completely at
+    /// `position` (a cell index, see `landmarks`), holds the closure for
+    /// `closure`, then reopens the tract over `release`.
+    /// The burst is produced when the obstruction opens,
+    /// and only if the velum is closed (see `set_velum_open`).
+    /// `position` is clamped to the oral tract.
+    pub fn plosive(&mut self, position: usize, closure: Duration, release: Duration) {
+        self.shaper
+            .plosive(position, closure.as_secs_f64(), release.as_secs_f64());
+    }
+
+    /// Pre-aspiration: replaces voicing with a puff of breath noise for
+    /// `duration`, e.g. before the voiced segment after a consonant.
+    /// The aspiration is as strong as for a fully breathy voice,
+    /// regardless of `intensity` and `target_tenseness`.
+    ///
+    /// Voicing is only muted, not stopped: `touched`, `note_on` and
+    /// `always_voice` keep driving `intensity` meanwhile, so voicing
+    /// resumes at the current intensity once `duration` has passed.
+    /// To voice after the puff, call `note_on` right after this.
+    pub fn pre_aspirate(&mut self, duration: Duration) {
+        self.glottis_mut().pre_aspirate(duration.as_secs_f64())
+    }
+
+    /// Speed at which the tongue body (the tract behind the nasal branch)
+    /// opens up, relative to `articulation_speed`. Towards the tongue tip
+    /// the speed ramps to `tongue_tip_speed`. Closing movements are always
+    /// twice as fast as `articulation_speed`. Default is 0.6.
+    pub fn tongue_body_speed(&self) -> f64 {
+        self.shaper.body_speed
+    }
+    pub fn set_tongue_body_speed(&mut self, speed: f64) {
+        self.shaper.body_speed = math::clamp_or(speed, 0.0, f64::MAX, self.shaper.body_speed)
+    }
+
+    /// Speed at which the tongue tip and lips open up,
+    /// relative to `articulation_speed`. Default is 1.
+    /// A higher ratio to `tongue_body_speed` gives crisper consonant releases.
+    pub fn tongue_tip_speed(&self) -> f64 {
+        self.shaper.tip_speed
+    }
+    pub fn set_tongue_tip_speed(&mut self, speed: f64) {
+        self.shaper.tip_speed = math::clamp_or(speed, 0.0, f64::MAX, self.shaper.tip_speed)
+    }
+
+    /// Narrowing of the tract held on top of the tongue position,
+    /// like touching the tract in the original Pink Trombone.
+    /// Turbulence noise is not added by itself, see `turbulence_points`.
+    /// Constrictions with a NaN position or diameter are ignored.
+    pub fn constriction(&self) -> Option<Constriction> {
+        self.shaper.constriction
+    }
+    pub fn set_constriction(&mut self, constriction: Option<Constriction>) {
+        if let Some(Constriction { index, diameter }) = constriction {
+            if index.is_nan() || diameter.is_nan() {
+                return;
+            }
+        }
+        self.shaper.constriction = constriction
+    }
+
+    /// Renders `gestures` one after another, appending the samples to `out`.
+    ///
+    /// Vowels glide from the previous tongue position over up to 60ms,
+    /// fricatives hold a constriction with turbulence noise and fade the
+    /// noise out over `fricative_release` afterwards, and plosives close
+    /// and reopen the tract (see `plosive`). Voicing is controlled with
+    /// `touched`, which is left as the last gesture set it; `always_voice`
+    /// is turned off for the duration of the call and restored afterwards.
+    pub fn say(&mut self, gestures: &[Gesture], out: &mut Vec<f32>) {
+        const TRANSITION: Duration = Duration::from_millis(60);
+        let always_voice = self.always_voice();
+        self.set_always_voice(false);
+        for gesture in gestures {
+            match *gesture {
+                Gesture::Vowel(phoneme, duration) => {
+                    self.set_touched(true);
+                    self.glide_to_phoneme(phoneme, TRANSITION.min(duration));
+                    out.extend(self.render(duration));
+                }
+                Gesture::Fricative {
+                    position,
+                    diameter,
+                    duration,
+                    voiced,
+                } => {
+                    self.set_touched(voiced);
+                    self.set_constriction(Some(Constriction {
+                        index: position,
+                        diameter,
+                    }));
+                    let tract = self.tract_mut();
+                    let (now, release) = (tract.time, tract.fricative_release_time() as f64);
+                    // drop noise from earlier fricatives once it faded out
+                    tract
+                        .turbulence_points
+                        .retain(|p| p.end_time.is_nan() || now - p.end_time < release);
+                    tract.turbulence_points.push(TurbulencePoint {
+                        diameter: diameter as f32,
+                        position: position as f32,
+                        start_time: now,
+                        end_time: f64::NAN,
+                    });
+                    let point = tract.turbulence_points.len() - 1;
+                    out.extend(self.render(duration));
+                    self.set_constriction(None);
+                    let tract = self.tract_mut();
+                    tract.turbulence_points[point].end_time = tract.time;
+                }
+                Gesture::Plosive {
+                    position,
+                    closure,
+                    release,
+                    voiced,
+                } => {
+                    self.set_touched(voiced);
+                    self.plosive(position, closure, release);
+                    out.extend(self.render(closure));
+                    self.set_touched(true);
+                    out.extend(self.render(release));
+                }
+                Gesture::Silence(duration) => {
+                    self.set_touched(false);
+                    out.extend(self.render(duration));
+                }
+            }
+        }
+        self.set_always_voice(always_voice);
+    }
+
+    /// How fast tract diameters move towards their targets,
+    /// in diameter units per second. Default is 15.
+    pub fn articulation_speed(&self) -> f64 {
+        self.shaper.movement_speed
+    }
+    pub fn set_articulation_speed(&mut self, articulation_speed: f64) {
+        self.shaper.movement_speed = math::clamp_or(
+            articulation_speed,
+            0.0,
+            f64::MAX,
+            self.shaper.movement_speed,
+        )
+    }
+
+    /// Spectral tilt applied to the glottal source, in dB per octave.
+    pub fn spectral_tilt(&self) -> f32 {
+        self.spectral_tilt.db_per_octave()
+    }
+    /// Negative values darken the voice, positive values brighten it,
+    /// independently of pitch and loudness.
+    /// The tilt is approximated with a first order filter,
+    /// and is clamped to -5.9..5.9. Default is 0 (no filtering).
+    pub fn set_spectral_tilt(&mut self, db_per_octave: f32) {
+        if db_per_octave.is_nan() {
+            return;
+        }
+        self.spectral_tilt
+            .set_db_per_octave(db_per_octave, self.sample_rate);
+    }
+
+    /// Whether the voice sounds regardless of `touched`. Default is `true`.
+    pub fn always_voice(&self) -> bool {
+        self.glottis().always_voice
+    }
+    pub fn set_always_voice(&mut self, always_voice: bool) {
+        self.glottis_mut().always_voice = always_voice
+    }
+
+    /// Whether voicing is requested. When `always_voice` is off,
+    /// intensity ramps up while touched and fades out otherwise.
+    pub fn touched(&self) -> bool {
+        self.glottis().is_touched
+    }
+    pub fn set_touched(&mut self, touched: bool) {
+        self.glottis_mut().is_touched = touched
+    }
+
+    /// When enabled, voicing builds up over the first few glottal cycles
+    /// after `touched` (or `note_on`) starts it, each pulse stronger than
+    /// the last, as in a real glottis. This is on top of the intensity
+    /// attack. Disabled by default.
+    pub fn natural_onset(&self) -> bool {
+        self.glottis().natural_onset
+    }
+    pub fn set_natural_onset(&mut self, natural_onset: bool) {
+        self.glottis_mut().natural_onset = natural_onset
+    }
+
+    /// Starts voicing the specified MIDI note.
+    ///
+    /// Turns `always_voice` off, so that `note_off` silences the voice,
+    /// and sets `target_tenseness` proportionally to `velocity`,
+    /// from 0 (breathy) to 0.9 at velocity 127.
+    /// Velocity 0 is treated as `note_off`, as is customary in MIDI.
+    pub fn note_on(&mut self, midi_note: u8, velocity: u8) {
+        if velocity == 0 {
+            self.note_off();
+            return;
+        }
+        const MAX_TENSENESS: f32 = 0.9;
+        self.set_musical_note(PinkTrombone::midi_note_to_semitone(midi_note));
+        self.set_target_tenseness(MAX_TENSENESS * velocity.min(127) as f32 / 127.0);
+        self.set_always_voice(false);
+        self.set_touched(true);
+    }
+
+    /// Stops voicing; intensity then fades out.
+    pub fn note_off(&mut self) {
+        self.set_touched(false);
     }
 
     /// 0..
     pub fn vibrato_gain(&self) -> f32 {
-        self.glottis().vibrato_amount
+        self.glottis().vibrato_amount()
     }
     pub fn set_vibrato_gain(&mut self, vibrato_gain: f32) {
-        self.glottis_mut().vibrato_amount = vibrato_gain
+        self.glottis_mut().set_vibrato_amount(vibrato_gain)
     }
 
     /// 0..
     pub fn vibrato_frequency(&self) -> f32 {
-        self.glottis().vibrato_frequency
+        self.glottis().vibrato_frequency()
     }
     pub fn set_vibrato_frequency(&mut self, vibrato_frequency: f32) {
-        self.glottis_mut().vibrato_frequency = vibrato_frequency
+        self.glottis_mut().set_vibrato_frequency(vibrato_frequency)
+    }
+
+    /// Whether vibrato and random pitch drift (including wobble) are applied.
+    /// When disabled, the pitch follows `target_frequency` exactly
+    /// once reached. Enabled by default.
+    pub fn vibrato_enabled(&self) -> bool {
+        self.glottis().vibrato_enabled
+    }
+    pub fn set_vibrato_enabled(&mut self, vibrato_enabled: bool) {
+        self.glottis_mut().vibrato_enabled = vibrato_enabled
+    }
+
+    /// Whether tenseness wanders slowly and randomly around
+    /// `target_tenseness`, varying the voice quality. When disabled, the
+    /// tenseness used is exactly `target_tenseness`, apart from the attack
+    /// at the start of voicing with `always_voice`. Enabled by default.
+    pub fn tenseness_drift(&self) -> bool {
+        self.glottis().tenseness_drift
+    }
+    pub fn set_tenseness_drift(&mut self, enabled: bool) {
+        self.glottis_mut().tenseness_drift = enabled
+    }
+
+    /// Whether all parameter smoothing is disabled. When frozen, the tract
+    /// shape, velum, intensity, frequency and tenseness are set exactly to
+    /// their targets at the next block, with no gliding, vibrato, random
+    /// drift or voicing attack, so steady configurations give steady,
+    /// analyzable output. This also disables the dynamics that make
+    /// the voice sound natural. Disabled by default.
+    pub fn frozen(&self) -> bool {
+        self.shaper.frozen
+    }
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.shaper.frozen = frozen;
+        self.glottis_mut().frozen = frozen;
+    }
+
+    /// Whether the glottal pulse excites the tract. When disabled, the
+    /// tract is excited only by aspiration and frication noise, for
+    /// whispers and unvoiced sounds. Aspiration noise still follows
+    /// `intensity` and `target_tenseness`: it is strongest with voicing on and
+    /// low tenseness, and silent at zero intensity. Enabled by default.
+    pub fn glottal_source_enabled(&self) -> bool {
+        self.glottal_source_enabled
+    }
+    pub fn set_glottal_source_enabled(&mut self, enabled: bool) {
+        self.glottal_source_enabled = enabled
+    }
+
+    /// One period of the glottal pulse waveform of the current cycle,
+    /// sampled at `points` evenly spaced phases over 0..1 (excluding 1),
+    /// e.g. for a wavetable synthesizer. The table is scaled by the current
+    /// `intensity` and `loudness`, as the pulses entering the tract are;
+    /// see `normalized_glottal_wavetable` for one peaking at about ±1.
+    /// Does not advance the synthesizer.
+    pub fn glottal_wavetable(&self, points: usize) -> Vec<f32> {
+        self.glottis().wavetable(points)
+    }
+
+    /// Same as `glottal_wavetable`, without `intensity` and `loudness`
+    /// applied, so that it does not depend on how loud the voice is.
+    pub fn normalized_glottal_wavetable(&self, points: usize) -> Vec<f32> {
+        self.glottis().normalized_wavetable(points)
+    }
+
+    /// Replaces the waveform of the glottal pulses, by default
+    /// `LfSource`, a Liljencrants-Fant model shaped by tenseness.
+    /// Unison voices use the new source too.
+    pub fn set_glottal_source(&mut self, source: Box<dyn GlottalSource>) {
+        self.unison.set_source(source.as_ref());
+        self.glottis_mut().set_source(source);
+    }
+
+    /// When enabled, the reflection of sound returning to the glottis
+    /// follows tenseness, from -0.8 when fully breathy to 0.8 when fully
+    /// tense, as in the original model, instead of the constant 0.75.
+    /// A more open (breathy) glottis then absorbs more of the returning
+    /// sound, damping the resonances, most audibly the first formant:
+    /// breathy voices sound duller and softer, tense ones ring more.
+    /// Disabled by default.
+    pub fn dynamic_glottal_reflection(&self) -> bool {
+        self.tract().dynamic_glottal_reflection
+    }
+    pub fn set_dynamic_glottal_reflection(&mut self, enabled: bool) {
+        self.tract_mut().dynamic_glottal_reflection = enabled
+    }
+
+    /// Whether a subglottal tube, modelling the trachea, is coupled to the
+    /// tract through the glottis. Off by default.
+    ///
+    /// While the glottis is open, part of the sound returning to it passes
+    /// into the tube instead of being reflected, and the tube resonances
+    /// (around 600Hz and 1.8kHz at 48kHz) feed back into the tract. This
+    /// adds the subglottal resonances and zeros of real voices to the low
+    /// end, most audibly for breathy voices, whose glottis reflects less
+    /// with `dynamic_glottal_reflection`. `tract_spectrum` and
+    /// `estimate_formants` do not include the tube.
+    pub fn subglottal_coupling(&self) -> bool {
+        self.tract().subglottal_coupling()
+    }
+    pub fn set_subglottal_coupling(&mut self, enabled: bool) {
+        self.tract_mut().set_subglottal_coupling(enabled)
+    }
+
+    /// Depth of the aspiration noise modulation: how strongly the breath
+    /// noise is pulsed by the glottal cycle and wobbles over time.
+    /// 0 gives smooth, steady aspiration, values above 1 make it more
+    /// pulsed. Frication noise is not affected. Defaults to 1.
+    /// Negative values are clamped to 0.
+    pub fn aspiration_modulation(&self) -> f32 {
+        self.glottis().aspiration_modulation()
+    }
+    pub fn set_aspiration_modulation(&mut self, depth: f32) {
+        self.glottis_mut().set_aspiration_modulation(depth)
     }
 
     pub fn vibrato_wobble(&self) -> bool {
@@ -116,82 +1156,2538 @@ impl PinkTrombone {
         self.shaper.set_velum_open(velum_open);
     }
 
-    pub fn turbulence_points(&mut self) -> &mut Vec<TurbulencePoint> {
-        &mut self.tract_mut().turbulence_points
+    /// How long changes of the tract shape are smoothed over,
+    /// in addition to the interpolation within each block.
+    pub fn diameter_smoothing(&self) -> Duration {
+        Duration::from_secs_f64(self.tract().reflection_smoothing())
+    }
+    /// Use to avoid clicks when the tract shape changes abruptly.
+    /// Reflection coefficients approach their new values exponentially
+    /// with this time constant. Default is zero (no extra smoothing).
+    pub fn set_diameter_smoothing(&mut self, smoothing: Duration) {
+        self.tract_mut()
+            .set_reflection_smoothing(smoothing.as_secs_f64());
     }
 
-    /// Set `target_frequency` to the specified musical note.
-    /// # Arguments
-    /// * `semitone` - semitone, based at A4.
-    pub fn set_musical_note(&mut self, semitone: f32) {
-        self.glottis_mut().set_musical_note(semitone)
+    /// Whether the piriform fossa side branch is simulated. Off by default.
+    /// The branch adds a spectral zero around a twentieth of the tract
+    /// sample rate (4.8kHz at 48kHz), making vowels sound more natural.
+    pub fn piriform_enabled(&self) -> bool {
+        self.tract().piriform_enabled()
+    }
+    pub fn set_piriform_enabled(&mut self, enabled: bool) {
+        self.tract_mut().set_piriform_enabled(enabled)
     }
 
-    pub fn synthesize(&mut self, buf: &mut [f32]) {
-        let mut p = 0;
-        while p < buf.len() {
-            let block_len = (buf.len() - p).min(PinkTrombone::MAX_BLOCK_LEN);
-            let block_buf = &mut buf[p..p + block_len];
-            self.synthesize_block(block_buf);
-            p += block_len;
+    /// Oral tract cell the piriform fossa side branch couples at.
+    /// Default is 3, just above the glottal end.
+    pub fn piriform_position(&self) -> usize {
+        self.tract().piriform_position()
+    }
+    /// Panics unless `1 <= position < landmarks::TRACT_LEN`,
+    /// and `position` differs from `nose_start()`.
+    pub fn set_piriform_position(&mut self, position: usize) {
+        self.tract_mut().set_piriform_position(position)
+    }
+
+    /// Cell index and diameter of the narrowest point of the oral tract,
+    /// not counting the glottal end. Ties resolve to the cell closest
+    /// to the glottis.
+    pub fn narrowest_constriction(&self) -> (usize, f64) {
+        let diameter = &self.tract().diameter;
+        let mut narrowest = Tract::GLOTTIS_END;
+        for i in Tract::GLOTTIS_END + 1..Tract::N {
+            if diameter[i] < diameter[narrowest] {
+                narrowest = i;
+            }
         }
+        (narrowest, diameter[narrowest])
     }
 
-    pub fn reset(&mut self) {
-        self.calculate_new_block_parameters(0.0);
+    /// Outline of the oral tract as a closed polygon, for plotting.
+    /// Points run along the upper wall from the glottis to the lips
+    /// (x = cell index, y = diameter / 2), then back along the lower wall
+    /// (y = -diameter / 2).
+    pub fn tract_outline(&self) -> Vec<(f32, f32)> {
+        outline(0, &self.tract().diameter)
     }
 
-    fn synthesize_block(&mut self, buf: &mut [f32]) {
-        let delta_time = buf.len() as f32 / self.sample_rate as f32;
-        self.calculate_new_block_parameters(delta_time);
-        for i in 0..buf.len() {
-            let lambda1 = i as f64 / buf.len() as f64;
-            let lambda2 = (i as f64 + 0.5) / buf.len() as f64;
-            let glottal_output = self.glottis_mut().step(lambda1 as f32) as f64;
-            let vocal1 = self.tract_mut().step(glottal_output, lambda1);
-            let vocal2 = self.tract_mut().step(glottal_output, lambda2);
-            buf[i] = (vocal1 + vocal2) * 0.125;
-        }
+    /// Outline of the nasal tract, in the same format as `tract_outline`.
+    /// x starts at `nose_start()`, so the cells line up with the oral tract
+    /// cells they run alongside, and y is centered on the nasal tract's
+    /// own axis.
+    pub fn nose_outline(&self) -> Vec<(f32, f32)> {
+        outline(self.nose_start(), self.tract().nose_diameter())
     }
 
-    fn calculate_new_block_parameters(&mut self, delta_time: f32) {
-        self.glottis_mut().adjust_parameters(delta_time);
-        self.shaper.adjust_tract_shape(delta_time as f64);
-        self.tract_mut().calculate_new_block_parameters();
+    /// Renders `tract_outline` and `nose_outline` as an SVG image,
+    /// with the nasal tract drawn above the oral tract.
+    #[cfg(feature = "svg")]
+    pub fn tract_svg(&self) -> String {
+        const SCALE: f32 = 10.0;
+        const NOSE_OFFSET: f32 = 4.0;
+        let points = |outline: Vec<(f32, f32)>, offset: f32| {
+            outline
+                .iter()
+                .map(|(x, y)| format!("{:.2},{:.2}", x * SCALE, -(y + offset) * SCALE))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let width = Tract::N as f32 * SCALE;
+        let top = -(NOSE_OFFSET + 2.5) * SCALE;
+        let height = (NOSE_OFFSET + 5.0) * SCALE;
+        format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 {} {} {}\">\n",
+                "<polygon points=\"{}\" fill=\"pink\" stroke=\"black\"/>\n",
+                "<polygon points=\"{}\" fill=\"pink\" stroke=\"black\"/>\n",
+                "</svg>\n"
+            ),
+            top,
+            width,
+            height,
+            points(self.tract_outline(), 0.0),
+            points(self.nose_outline(), NOSE_OFFSET),
+        )
     }
 
-    fn tract(&self) -> &Tract {
-        &self.shaper.tract
+    /// A multi-line summary of the key synthesis state, for bug reports
+    /// and teaching. The format is meant for reading and may change.
+    pub fn debug_state(&self) -> String {
+        let glottis = self.glottis();
+        format!(
+            concat!(
+                "sample rate: {} Hz (tract {} Hz)\n",
+                "time: {:?}\n",
+                "frequency: target {:.2} Hz, smooth {:.2} Hz, current {:.2} Hz\n",
+                "tenseness: target {:.3}, current {:.3}\n",
+                "intensity: {:.3}\n",
+                "loudness: {:.3} ({:?})\n",
+                "tongue: index {:.2}, diameter {:.3}\n",
+                "velum: opening {:.3}, target {:.3}\n",
+                "active transients: {}\n",
+                "turbulence points: {}\n"
+            ),
+            self.sample_rate,
+            self.tract_sample_rate(),
+            self.time(),
+            self.target_frequency(),
+            glottis.smooth_frequency,
+            self.current_frequency(),
+            self.target_tenseness(),
+            self.current_tenseness(),
+            glottis.intensity(),
+            glottis.loudness(),
+            glottis.loudness_mode(),
+            self.tongue_index(),
+            self.tongue_diameter(),
+            self.velum_opening(),
+            self.velum_target(),
+            self.active_transients().len(),
+            self.tract().turbulence_points.len(),
+        )
     }
 
-    fn glottis(&self) -> &Glottis {
-        &self.tract().glottis
+    /// Current velum opening, the diameter of the entrance to the nasal tract.
+    /// Moves gradually towards `velum_target`.
+    pub fn velum_opening(&self) -> f64 {
+        self.tract().nose_diameter()[0]
     }
 
-    fn glottis_mut(&mut self) -> &mut Glottis {
-        &mut self.tract_mut().glottis
+    /// Velum opening set by `set_velum_open`: 0.4 when open, 0.01 when closed.
+    pub fn velum_target(&self) -> f64 {
+        self.shaper.velum_target()
     }
 
-    fn tract_mut(&mut self) -> &mut Tract {
-        &mut self.shaper.tract
+    /// Whether `velum_opening` has reached `velum_target`,
+    /// so the nasal coupling no longer changes.
+    pub fn velum_settled(&self) -> bool {
+        (self.velum_opening() - self.velum_target()).abs() < 1e-6
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // Note this useful idiom: importing names from outer (for mod tests) scope.
-    use super::*;
-    use crate::rng::xorshift;
+    /// Oral tract cell the nasal tract branches off at.
+    /// Default is `landmarks::NOSE_START`.
+    pub fn nose_start(&self) -> usize {
+        self.tract().nose_start()
+    }
+    /// Moves the velar coupling point of the nasal tract.
+    /// Panics unless `1 <= nose_start < landmarks::TRACT_LEN - 1`,
+    /// or if it collides with the enabled piriform fossa branch.
+    /// Tongue and lip articulation is unaffected.
+    pub fn set_nose_start(&mut self, nose_start: usize) {
+        self.tract_mut().set_nose_start(nose_start)
+    }
 
-    const SAMPLE_RATE: u32 = 48000;
-    const SEED: u16 = 9452;
+    /// Reflection coefficients between oral tract cells `i - 1` and `i`,
+    /// reached at the end of the last synthesized block. Index 0 is unused.
+    /// They are derived from the cell areas, `(a[i-1] - a[i]) / (a[i-1] + a[i])`,
+    /// smoothed over `diameter_smoothing`.
+    pub fn reflection_coefficients(&self) -> &[f64] {
+        self.tract().new_reflection()
+    }
+    /// Reflection coefficients at the start of the last synthesized block.
+    /// Samples within the block interpolate towards `reflection_coefficients`.
+    pub fn previous_reflection_coefficients(&self) -> &[f64] {
+        self.tract().reflection()
+    }
 
-    #[test]
-    fn reproducible() {
-        let mut random = xorshift::XorShift128::new(SEED.into());
-        let mut trombone = PinkTrombone::new(SAMPLE_RATE, &mut random, SEED);
-        let mut buffer = vec![0.0; SAMPLE_RATE as usize * 15];
-        trombone.synthesize(&mut buffer);
-        assert_eq!(format!("{:.10}", buffer.last().unwrap()), "0.0385491103");
+    /// Reflection coefficients of the junction where the nasal tract
+    /// branches off, at the end of the last synthesized block.
+    pub fn nose_junction_reflections(&self) -> NoseJunction {
+        self.tract().new_nose_junction()
+    }
+    /// Like `nose_junction_reflections`, at the start of the last block.
+    pub fn previous_nose_junction_reflections(&self) -> NoseJunction {
+        self.tract().nose_junction()
+    }
+
+    /// Reflection coefficients between nasal tract cells `i - 1` and `i`.
+    /// Index 0 is unused, the velum being covered by the nasal junction.
+    pub fn nose_reflection_coefficients(&self) -> &[f64] {
+        self.tract().nose_reflection()
+    }
+
+    /// Nasal tract cell diameters, the first one being the velum opening.
+    pub fn nose_profile(&self) -> &[f64] {
+        self.tract().nose_diameter()
+    }
+    /// Overrides nasal tract diameters. The length of `profile` becomes the
+    /// number of nasal tract cells (at least 2, default is 28).
+    /// `profile[0]` is ignored, as the velum is controlled by `set_velum_open`.
+    /// Changing the nasal tract length silences it.
+    pub fn set_nose_profile(&mut self, profile: &[f64]) {
+        self.shaper.set_nose_profile(profile);
+    }
+    /// Changes the number of nasal tract cells (at least 2, default is 28),
+    /// restoring the default nasal diameter profile. Silences the nasal tract.
+    pub fn set_nose_length(&mut self, nose_len: usize) {
+        self.shaper.set_nose_len(nose_len);
+    }
+
+    /// Whether the voice is off and the tract has rung down,
+    /// e.g. to stop rendering after `note_off`.
+    ///
+    /// This is a heuristic based on the tract's peak amplitude trackers,
+    /// which decay slowly (by a factor of 0.9999 per tract sample),
+    /// scaled to the output level and compared to `threshold`.
+    pub fn is_silent(&self, threshold: f32) -> bool {
+        const OUTPUT_GAIN: f64 = 0.25;
+        let level = self.tract().max_amplitude() * OUTPUT_GAIN * self.output_gain.abs() as f64;
+        self.intensity() < 1e-6 && level < threshold as f64
+    }
+
+    /// Oral (lip) component of the last tract sample, before output gain.
+    /// Together with `nose_output` it sums to the tract output.
+    pub fn lip_output(&self) -> f32 {
+        self.tract().last_lip_output as f32
+    }
+
+    /// Nasal component of the last tract sample, before output gain.
+    pub fn nose_output(&self) -> f32 {
+        self.tract().last_nose_output as f32
+    }
+
+    /// How long turbulence noise takes to fade in after a turbulence point
+    /// starts. Default is 100ms.
+    pub fn fricative_attack(&self) -> Duration {
+        Duration::from_secs_f32(self.tract().fricative_attack_time())
+    }
+    pub fn set_fricative_attack(&mut self, attack: Duration) {
+        self.tract_mut()
+            .set_fricative_attack_time(attack.as_secs_f32())
+    }
+
+    /// How long turbulence noise takes to fade out after a turbulence point
+    /// ends. Default is 100ms.
+    pub fn fricative_release(&self) -> Duration {
+        Duration::from_secs_f32(self.tract().fricative_release_time())
+    }
+    pub fn set_fricative_release(&mut self, release: Duration) {
+        self.tract_mut()
+            .set_fricative_release_time(release.as_secs_f32())
+    }
+
+    /// How turbulence noise is spread over the tract cells around
+    /// each turbulence point. Default is `TurbulenceDistribution::TwoPoint`.
+    pub fn turbulence_distribution(&self) -> TurbulenceDistribution {
+        self.tract().turbulence_distribution
+    }
+    pub fn set_turbulence_distribution(&mut self, distribution: TurbulenceDistribution) {
+        self.tract_mut().turbulence_distribution = distribution
+    }
+
+    /// Regenerates the aspiration and frication white noise loops
+    /// with `loop_size` samples each, drawn from `rng`.
+    ///
+    /// The noise repeats every `loop_size` samples (at the output rate for
+    /// aspiration, at the tract rate for frication), which can become audible
+    /// as periodicity on long sustained sounds if the loop is short.
+    /// Each loop costs `loop_size` `f64`s of memory, so the default of
+    /// 32768 samples takes 256KB per source.
+    pub fn set_noise_loop_size(&mut self, loop_size: usize, rng: &mut dyn NoiseSource<f64>) {
+        self.set_noise_mode(NoiseMode::Looped(loop_size), rng);
+    }
+
+    /// Regenerates the aspiration and frication white noise sources
+    /// from independent streams seeded by a single draw from `rng`.
+    ///
+    /// The output is reproducible for a given `rng` state,
+    /// but `NoiseMode::Live` will not match the output of `NoiseMode::Looped`.
+    pub fn set_noise_mode(&mut self, mode: NoiseMode, rng: &mut dyn NoiseSource<f64>) {
+        let (mut aspiration_rng, mut frication_rng) = NoiseStream::split(rng);
+        self.glottis_mut().set_noise_mode(mode, &mut aspiration_rng);
+        self.tract_mut().set_noise_mode(mode, &mut frication_rng);
+    }
+
+    /// Like `set_noise_mode`, drawing from the RNG kept by
+    /// `with_owned_rng`. Returns `false`, leaving the noise unchanged,
+    /// if the synthesizer was not created with it.
+    pub fn regenerate_noise(&mut self, mode: NoiseMode) -> bool {
+        let Some(mut owned_rng) = self.owned_rng.take() else {
+            return false;
+        };
+        owned_rng.regenerate(self, mode);
+        self.owned_rng = Some(owned_rng);
+        true
+    }
+
+    /// Re-randomizes the aspiration and frication noise and the random
+    /// pitch and tenseness drift from `seed`, keeping the noise mode, the
+    /// tract state, and all targets. The same state reseeded with the same
+    /// `seed` produces the same output. The noise texture changes abruptly,
+    /// but pitch and articulation continue smoothly.
+    pub fn reseed(&mut self, seed: u64) {
+        let drift_seed = (seed ^ (seed >> 16) ^ (seed >> 32) ^ (seed >> 48)) as u16;
+        self.glottis_mut()
+            .reseed(drift_seed, &mut NoiseStream::Aspiration.rng(seed));
+        self.tract_mut()
+            .reseed(&mut NoiseStream::Frication.rng(seed));
+    }
+
+    /// Replaces the aspiration and frication noise with new noise derived
+    /// from `seed`, in place and without allocating. Unlike `reseed`, the
+    /// noise filters and the position in looped noise carry on, so there
+    /// is no click, and the pitch and tenseness drift are unchanged.
+    /// Use to vary the noise texture over long renders.
+    pub fn refill_noise(&mut self, seed: u64) {
+        self.glottis_mut()
+            .refill_noise(&mut NoiseStream::Aspiration.rng(seed));
+        self.tract_mut()
+            .refill_noise(&mut NoiseStream::Frication.rng(seed));
+    }
+
+    /// Transients currently sounding, such as plosive bursts.
+    /// Start times are in the time base of `time()`.
+    pub fn active_transients(&self) -> &[Transient] {
+        self.tract().transients()
+    }
+
+    /// Sources of turbulence noise along the tract, such as the
+    /// constrictions of fricatives, with times in the time base of `time()`.
+    /// To change them, e.g. to end a point, pass a modified copy
+    /// to `set_turbulence_points`, which validates them.
+    pub fn turbulence_points(&self) -> &[TurbulencePoint] {
+        &self.tract().turbulence_points
+    }
+
+    /// Replaces all turbulence points with the valid ones among `points`
+    /// (see `TurbulencePoint::is_valid`), e.g. to load a saved scene.
+    /// Returns the indices in `points` of the rejected ones.
+    pub fn set_turbulence_points(&mut self, points: Vec<TurbulencePoint>) -> Vec<usize> {
+        let mut rejected = Vec::new();
+        let mut valid = Vec::with_capacity(points.len());
+        for (index, point) in points.into_iter().enumerate() {
+            if point.is_valid() {
+                valid.push(point);
+            } else {
+                rejected.push(index);
+            }
+        }
+        self.tract_mut().turbulence_points = valid;
+        rejected
+    }
+
+    /// Current intensity of the noise of each turbulence point, 0..1,
+    /// in the order of `turbulence_points`: rising over `fricative_attack`
+    /// after the point starts and falling over `fricative_release` after
+    /// it ends. E.g. to show the state of consonant articulation.
+    /// Points outside of the tract or fully closed make no noise regardless.
+    pub fn turbulence_intensities(&self) -> Vec<f32> {
+        self.tract().turbulence_intensities()
+    }
+
+    /// Set `target_frequency` to the specified musical note.
+    /// # Arguments
+    /// * `semitone` - semitone, based at A4.
+    pub fn set_musical_note(&mut self, semitone: f32) {
+        self.set_target_frequency(math::semitone_to_hz(semitone))
+    }
+
+    /// Converts a frequency in Hz to semitones from A4 (440 Hz).
+    pub fn hz_to_semitone(hz: f32) -> f32 {
+        math::hz_to_semitone(hz)
+    }
+
+    /// Converts semitones from A4 (440 Hz) to a frequency in Hz.
+    pub fn semitone_to_hz(semitone: f32) -> f32 {
+        math::semitone_to_hz(semitone)
+    }
+
+    /// Estimates the fundamental frequency in Hz of `samples`, such as the
+    /// output of `synthesize` or a recording of a singer, e.g. to tune
+    /// `target_frequency` to it. Returns `None` for silent or unvoiced
+    /// input, like noise or whispering.
+    ///
+    /// Pitches from 50 to 1000 Hz are detected, from at least two periods
+    /// of the input; longer input is more robust. The estimate is the
+    /// average over the input, within about 0.2% for a steady tone.
+    /// It uses autocorrelation rather than an FFT, and takes time
+    /// proportional to the length of the input times `sample_rate / 50`.
+    pub fn detect_pitch(samples: &[f32], sample_rate: u32) -> Option<f32> {
+        pitch::detect(samples, sample_rate)
+    }
+
+    /// Converts a MIDI note number to semitones from A4 (MIDI note 69),
+    /// as accepted by `set_musical_note`.
+    pub fn midi_note_to_semitone(note: u8) -> f32 {
+        note as f32 - 69.0
+    }
+
+    /// Queue parameter changes to be applied together at the start of the
+    /// next `fill`. Changes queued before that are merged, newer ones winning.
+    pub fn set_params_pending(&mut self, params: Params) {
+        self.pending.merge(params);
+    }
+
+    /// Applies parameter changes queued with `set_params_pending`,
+    /// then synthesizes into `buf` like `synthesize`.
+    pub fn fill(&mut self, buf: &mut [f32]) {
+        self.apply_pending();
+        self.synthesize(buf);
+    }
+
+    fn apply_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let params = std::mem::take(&mut self.pending);
+        if let Some(frequency) = params.target_frequency {
+            self.set_target_frequency(frequency);
+        }
+        if let Some(tenseness) = params.target_tenseness {
+            self.set_target_tenseness(tenseness);
+        }
+        if let Some(intensity) = params.intensity {
+            self.set_intensity(intensity);
+        }
+        if let Some(index) = params.tongue_index {
+            self.set_tongue_index(index);
+        }
+        if let Some(diameter) = params.tongue_diameter {
+            self.set_tongue_diameter(diameter);
+        }
+        if let Some(velum_open) = params.velum_open {
+            self.set_velum_open(velum_open);
+        }
+    }
+
+    pub fn synthesize(&mut self, buf: &mut [f32]) {
+        if buf.is_empty() {
+            return;
+        }
+        self.begin_call();
+        let mut p = 0;
+        while p < buf.len() {
+            let block_len = (buf.len() - p).min(self.block_len);
+            let block_buf = &mut buf[p..p + block_len];
+            self.synthesize_block(block_buf, Excitation::Glottis);
+            p += block_len;
+        }
+    }
+
+    /// Synthesizes a single sample, for callers that process audio one
+    /// sample at a time.
+    ///
+    /// A whole block (see `block_size`) is synthesized ahead whenever the
+    /// previous one runs out, so the samples are bit-identical to those of
+    /// `synthesize` called with buffers of `block_size`, and parameter
+    /// changes take effect at the next block like with `synthesize`.
+    /// Other synthesis calls drop the rest of the block synthesized ahead.
+    pub fn next_sample(&mut self) -> f32 {
+        if self.sample_buffer_pos == self.sample_buffer.len() {
+            let mut buffer = std::mem::take(&mut self.sample_buffer);
+            buffer.resize(self.block_len, 0.0);
+            self.synthesize(&mut buffer);
+            self.sample_buffer = buffer;
+            self.sample_buffer_pos = 0;
+        }
+        let sample = self.sample_buffer[self.sample_buffer_pos];
+        self.sample_buffer_pos += 1;
+        sample
+    }
+
+    /// Synthesize `count` samples into the ring buffer `ring`, starting at
+    /// `*write_pos` and wrapping around its end, for continuous streaming.
+    /// `write_pos` is advanced past the written samples, modulo `ring.len()`.
+    /// When `count` exceeds `ring.len()`, the oldest of the new samples
+    /// are overwritten, leaving the last `ring.len()` of them.
+    /// Panics if `ring` is empty and `count` is not 0.
+    pub fn synthesize_into_ring(&mut self, ring: &mut [f32], write_pos: &mut usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        if ring.is_empty() {
+            panic!("ring buffer must not be empty");
+        }
+        self.begin_call();
+        *write_pos %= ring.len();
+        let mut remaining = count;
+        while remaining > 0 {
+            let start = *write_pos;
+            let block_len = remaining.min(ring.len() - start).min(self.block_len);
+            self.synthesize_block(&mut ring[start..start + block_len], Excitation::Glottis);
+            remaining -= block_len;
+            *write_pos = (start + block_len) % ring.len();
+        }
+    }
+
+    /// Synthesize a unison of `voices` glottal sources sharing the tract,
+    /// for a richer, chorus-like sound.
+    ///
+    /// Besides the main glottis, `voices - 1` extra glottal sources follow
+    /// its parameters, detuned evenly over `-detune_cents..detune_cents`,
+    /// with their glottal cycles offset from each other and independent
+    /// pitch drift. Their glottal pulses are averaged, and aspiration
+    /// noise from the main glottis added, before entering the tract.
+    /// Extra voices are kept between calls, and only rebuilt (allocating)
+    /// when `voices` changes; `synthesize` ignores them.
+    /// Panics if `voices` is 0.
+    pub fn synthesize_unison(&mut self, buf: &mut [f32], voices: usize, detune_cents: f32) {
+        if voices == 0 {
+            panic!("unison needs at least one voice");
+        }
+        self.unison.configure(
+            voices - 1,
+            detune_cents,
+            &self.shaper.tract.glottis,
+            self.sample_rate,
+        );
+        self.begin_call();
+        let mut p = 0;
+        while p < buf.len() {
+            let block_len = (buf.len() - p).min(self.block_len);
+            let block_buf = &mut buf[p..p + block_len];
+            self.synthesize_block(block_buf, Excitation::Unison);
+            p += block_len;
+        }
+    }
+
+    /// Same as `synthesize`, but calls `on_block` before each internal block
+    /// (see `set_block_size`), so custom articulation logic can update
+    /// parameters at block cadence, e.g. based on `time()`.
+    ///
+    /// `on_block` may call any setter or query, but must not synthesize:
+    /// calling `synthesize` or one of its variants from it would interleave
+    /// its output with this call's and reset glottal closure tracking.
+    pub fn synthesize_with<F: FnMut(&mut PinkTrombone)>(
+        &mut self,
+        buf: &mut [f32],
+        mut on_block: F,
+    ) {
+        self.begin_call();
+        let mut p = 0;
+        while p < buf.len() {
+            on_block(self);
+            let block_len = (buf.len() - p).min(self.block_len);
+            let block_buf = &mut buf[p..p + block_len];
+            self.synthesize_block(block_buf, Excitation::Glottis);
+            p += block_len;
+        }
+    }
+
+    /// Enables or disables tracking of glottal closure instants,
+    /// the output samples at which a new glottal cycle begins.
+    /// Disabled by default.
+    pub fn set_track_glottal_closures(&mut self, track: bool) {
+        if track != self.glottal_closures.is_some() {
+            self.glottal_closures = if track { Some(Vec::new()) } else { None };
+        }
+    }
+
+    /// Takes the glottal closure instants from the last `synthesize` call,
+    /// as sample offsets into its buffer.
+    /// Always empty unless enabled with `set_track_glottal_closures`.
+    pub fn take_glottal_closures(&mut self) -> Vec<usize> {
+        match &mut self.glottal_closures {
+            Some(closures) => std::mem::take(closures),
+            None => Vec::new(),
+        }
+    }
+
+    /// Synthesize using `source` as the glottal excitation instead of
+    /// the built-in glottis model, which turns the synthesizer into an
+    /// articulatory filter. Tract shaping, transients and turbulence noise
+    /// still run as usual; spectral tilt is not applied to `source`.
+    ///
+    /// `source` is sampled at the output sample rate. If it is shorter
+    /// than `out`, the excitation is silent past its end;
+    /// extra `source` samples are ignored.
+    pub fn synthesize_with_source(&mut self, source: &[f32], out: &mut [f32]) {
+        self.begin_call();
+        let mut p = 0;
+        while p < out.len() {
+            let block_len = (out.len() - p).min(self.block_len);
+            let block_source = &source[p.min(source.len())..];
+            let block_out = &mut out[p..p + block_len];
+            self.synthesize_block(block_out, Excitation::Source(block_source));
+            p += block_len;
+        }
+    }
+
+    /// Response of the vocal tract in its current shape to a unit impulse
+    /// of the glottal source, as `len` output samples.
+    ///
+    /// The impulse is fed to the tract the same way `synthesize` feeds the
+    /// glottal source, and tract samples are averaged to output samples.
+    /// Tract parameters are frozen, glottal excitation, transients
+    /// and turbulence noise are disabled during the probe,
+    /// and the synthesizer state is left unchanged.
+    /// Apply an FFT to the result to get the tract's transfer function.
+    pub fn impulse_response(&mut self, len: usize) -> Vec<f32> {
+        let ratio = self.tract_resampling_ratio();
+        if ratio == 1.0 {
+            let tract_response = self.tract_mut().impulse_response(2 * len);
+            // the glottal source is held for the two tract samples
+            // of each output sample
+            let held =
+                |k: usize| tract_response[k] + if k > 0 { tract_response[k - 1] } else { 0.0 };
+            return (0..len)
+                .map(|n| (held(2 * n) + held(2 * n + 1)) * 0.125)
+                .collect();
+        }
+
+        let hold = (2.0 * ratio).round().max(1.0) as usize;
+        let tract_len = (2 * len) as f64 * ratio;
+        let tract_response = self.tract_mut().impulse_response(tract_len as usize + 2);
+        let held: Vec<f32> = (0..tract_response.len())
+            .map(|k| tract_response[k.saturating_sub(hold - 1)..=k].iter().sum())
+            .collect();
+        let resampled = |k: usize| {
+            let position = k as f64 * ratio;
+            let index = position as usize;
+            let t = (position - index as f64) as f32;
+            math::interpolate(held[index], held[index + 1], t)
+        };
+        (0..len)
+            .map(|n| (resampled(2 * n) + resampled(2 * n + 1)) * 0.125)
+            .collect()
+    }
+
+    /// Maximum number of samples synthesized between
+    /// recalculations of the synthesis parameters.
+    pub fn block_size(&self) -> usize {
+        self.block_len
+    }
+    /// Parameter changes (articulation, frequency, etc.) take effect at
+    /// block boundaries, so smaller blocks give finer-grained control
+    /// at the cost of more CPU time spent recalculating parameters.
+    /// Defaults to 512. Values below 16 are clamped to 16.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_len = block_size.max(PinkTrombone::MIN_BLOCK_LEN);
+    }
+
+    /// How tract samples are reduced to output samples in `synthesize`.
+    pub fn decimation(&self) -> DecimationMode {
+        self.decimator.mode()
+    }
+    /// Defaults to `DecimationMode::Average`, which reproduces
+    /// the output of the original Pink Trombone.
+    /// Changing the mode resets the decimation filter state.
+    pub fn set_decimation(&mut self, mode: DecimationMode) {
+        if mode != self.decimator.mode() {
+            self.decimator = Decimator::new(mode);
+        }
+    }
+
+    /// Synthesize at the tract sample rate (see `tract_sample_rate`).
+    ///
+    /// `buf.len()` must be even: it receives two tract samples per output
+    /// sample, in time order, with the same output gain as `synthesize`.
+    /// By default `synthesize` produces each output sample by averaging
+    /// such a pair, which is a crude 2-tap decimation filter
+    /// (see `set_decimation`); use this method to apply your own
+    /// anti-aliasing filter instead.
+    pub fn synthesize_oversampled(&mut self, buf: &mut [f32]) {
+        if !buf.len().is_multiple_of(2) {
+            panic!("oversampled buffer length must be even");
+        }
+        self.begin_call();
+        let mut p = 0;
+        while p < buf.len() {
+            let block_len = ((buf.len() - p) / 2).min(self.block_len);
+            let block_buf = &mut buf[p..p + 2 * block_len];
+            self.synthesize_oversampled_block(block_buf);
+            p += 2 * block_len;
+        }
+    }
+
+    /// Same as `synthesize`, and returns an estimate of the true peak of
+    /// `buf`, the highest magnitude of the continuous signal a DAC
+    /// reconstructs from it, for metering against a ceiling below full
+    /// scale. It can exceed the highest sample, as peaks fall between them.
+    ///
+    /// The tract runs at twice the output rate, and the estimate is the
+    /// highest magnitude of both its samples and the output ones, with no
+    /// extra oversampling. Peaks between the tract samples are still
+    /// missed, by up to about 0.7 dB for the highest frequencies. The tract
+    /// samples are taken before decimation removes content above the
+    /// output Nyquist frequency, which may overestimate the peak, and
+    /// without the DC blocker and gain control, so the estimate is rough
+    /// when those are enabled.
+    pub fn synthesize_true_peak(&mut self, buf: &mut [f32]) -> f32 {
+        if buf.is_empty() {
+            return 0.0;
+        }
+        self.begin_call();
+        let mut peak = 0.0_f32;
+        let mut p = 0;
+        while p < buf.len() {
+            let block_len = (buf.len() - p).min(self.block_len);
+            let block_buf = &mut buf[p..p + block_len];
+            peak = peak.max(self.synthesize_block(block_buf, Excitation::Glottis));
+            p += block_len;
+        }
+        buf.iter().fold(peak, |peak, x| peak.max(x.abs()))
+    }
+
+    /// Same as `synthesize`, but reports the first sample that is NaN
+    /// or infinite. Useful for debugging extreme parameter values.
+    /// Once the output becomes non-finite it usually stays non-finite.
+    ///
+    /// Like `debug_assert!`, the check only runs in builds with debug
+    /// assertions enabled; otherwise this is `synthesize` returning `Ok`.
+    pub fn synthesize_checked(&mut self, buf: &mut [f32]) -> Result<(), NonFinite> {
+        self.synthesize(buf);
+        if !cfg!(debug_assertions) {
+            return Ok(());
+        }
+        match buf.iter().position(|x| !x.is_finite()) {
+            Some(index) => Err(NonFinite { index }),
+            None => Ok(()),
+        }
+    }
+
+    /// Same as `synthesize`, and returns the approximate spectral centroid
+    /// of `buf` in Hz, as a brightness measure; 0 for silence.
+    ///
+    /// This is not an FFT centroid: it is the frequency of the pure tone
+    /// with the same ratio of high-passed (first difference) to full-band
+    /// energy as `buf`. It follows the centroid closely for narrow spectra,
+    /// and rises and falls with it for voice, so it is meant for
+    /// comparisons and control rather than measurement.
+    pub fn synthesize_with_centroid(&mut self, buf: &mut [f32]) -> f32 {
+        self.synthesize(buf);
+        brightness_centroid(buf, self.sample_rate)
+    }
+
+    /// Same as `synthesize`, and fills `env` with the amplitude envelope of
+    /// `buf`: the rectified output smoothed by a one-pole filter (see
+    /// `set_envelope_smoothing`), e.g. to drive mouth animation.
+    /// The envelope continues smoothly across calls.
+    /// Fails without synthesizing if `env` and `buf` differ in length.
+    pub fn synthesize_with_envelope(
+        &mut self,
+        buf: &mut [f32],
+        env: &mut [f32],
+    ) -> Result<(), LengthMismatch> {
+        if env.len() != buf.len() {
+            return Err(LengthMismatch {
+                expected: buf.len(),
+                actual: env.len(),
+            });
+        }
+        self.synthesize(buf);
+        for (level, &x) in env.iter_mut().zip(buf.iter()) {
+            *level = self.envelope.process(x);
+        }
+        Ok(())
+    }
+
+    /// Time constant of the envelope from `synthesize_with_envelope`.
+    /// Longer times give a steadier, but more sluggish envelope.
+    /// Defaults to 20ms.
+    pub fn envelope_smoothing(&self) -> Duration {
+        self.envelope.smoothing()
+    }
+    pub fn set_envelope_smoothing(&mut self, smoothing: Duration) {
+        self.envelope = EnvelopeFollower::new(smoothing, self.sample_rate);
+    }
+
+    /// Same as `synthesize`, followed by the pre-emphasis filter
+    /// `y[n] = x[n] - coeff * x[n-1]` commonly expected by speech
+    /// recognition and feature extraction, with `coeff` typically 0.97.
+    /// The previous sample is kept across calls.
+    ///
+    /// The output of `synthesize` already is the sound radiated from the
+    /// lips, so this is not needed for playback: pre-emphasis boosts highs
+    /// by another ~6 dB/octave, sounding thin. Use `set_spectral_tilt`
+    /// to change the timbre instead.
+    pub fn synthesize_preemphasis(&mut self, buf: &mut [f32], coeff: f32) {
+        self.synthesize(buf);
+        for x in buf.iter_mut() {
+            let input = *x;
+            *x = input - coeff * self.preemphasis_last;
+            self.preemphasis_last = input;
+        }
+    }
+
+    /// Same as `synthesize`, but produces 16-bit PCM samples.
+    /// Samples outside of -1..1 are clipped.
+    pub fn synthesize_i16(&mut self, buf: &mut [i16]) {
+        self.synthesize_each(buf.len(), |i, x| buf[i] = pcm::to_i16(x));
+    }
+
+    /// Same as `synthesize`, but produces 8-bit G.711 mu-law samples,
+    /// as used in telephony. Samples outside of -1..1 are clipped.
+    pub fn synthesize_mulaw(&mut self, buf: &mut [u8]) {
+        self.synthesize_each(buf.len(), |i, x| buf[i] = pcm::to_mulaw(x));
+    }
+
+    /// Synthesize a pseudo-stereo signal by panning the oral (lip) and nasal
+    /// outputs of the tract towards opposite channels.
+    ///
+    /// This is an effect, not a physically accurate model.
+    /// `width` is clamped to 0..1: with 0 both channels are identical to the
+    /// output of `synthesize`, with 1 the left channel only carries
+    /// the oral output and the right channel only the nasal output.
+    /// Always uses `DecimationMode::Average`.
+    /// `left` and `right` must have the same length.
+    pub fn synthesize_stereo(&mut self, left: &mut [f32], right: &mut [f32], width: f32) {
+        if left.len() != right.len() {
+            panic!("left and right buffers must have the same length");
+        }
+        let width = width.clamp(0.0, 1.0);
+        self.begin_call();
+        let mut p = 0;
+        while p < left.len() {
+            let block_len = (left.len() - p).min(self.block_len);
+            let block_left = &mut left[p..p + block_len];
+            let block_right = &mut right[p..p + block_len];
+            let fade = self.startup_fade;
+            let output_gain = self.output_gain;
+            self.step_block(block_len, Excitation::Glottis, |i, s1, s2| {
+                let vocal = s1.vocal + s2.vocal;
+                let side = width * ((s1.lip - s1.nose) + (s2.lip - s2.nose));
+                let gain = 0.125 * fade.gain(i) * output_gain;
+                block_left[i] = (vocal + side) * gain;
+                block_right[i] = (vocal - side) * gain;
+            });
+            p += block_len;
+        }
+    }
+
+    /// Synthesize `duration` worth of samples into a new buffer.
+    pub fn render(&mut self, duration: Duration) -> Vec<f32> {
+        let len = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        let mut buf = vec![0.0; len];
+        self.synthesize(&mut buf);
+        buf
+    }
+
+    /// Advances the simulation by `dt`, appending the samples for it to
+    /// `out`, e.g. from a game loop with irregular frame times.
+    ///
+    /// Each call is synthesized as a single block, so parameters changed
+    /// before the call are interpolated over exactly `dt` rather than over
+    /// `block_size` samples. Time that does not add up to a whole sample
+    /// is carried over to the next call, so over many calls the number of
+    /// samples matches the total time exactly.
+    pub fn advance(&mut self, dt: Duration, out: &mut Vec<f32>) {
+        const NANOS_PER_SECOND: u128 = 1_000_000_000;
+        let elapsed = self.advance_remainder + dt.as_nanos() * self.sample_rate as u128;
+        let len = (elapsed / NANOS_PER_SECOND) as usize;
+        self.advance_remainder = elapsed % NANOS_PER_SECOND;
+        if len == 0 {
+            return;
+        }
+        let start = out.len();
+        out.resize(start + len, 0.0);
+        self.begin_call();
+        self.synthesize_block(&mut out[start..], Excitation::Glottis);
+    }
+
+    /// Same as `synthesize` for `count` samples, pushing them into `sink`
+    /// instead of a slice. Samples are passed to `sink` as they are
+    /// produced, so this does not allocate.
+    pub fn synthesize_to<S: AudioSink>(&mut self, sink: &mut S, count: usize) {
+        self.synthesize_each(count, |_, x| sink.push(x));
+    }
+
+    /// Advances time by `duration` without synthesizing, for sparse
+    /// rendering. The glottal cycle, vibrato and drift, parameter smoothing
+    /// and tract movements progress block by block as if `duration` had
+    /// been rendered, so that they continue smoothly afterwards.
+    ///
+    /// Unlike rendering silence, this does not let the tract ring down:
+    /// the sound in it is frozen during the skip and resumes where it was.
+    /// Output filters and gain control are frozen too. Much cheaper than
+    /// `synthesize`, as neither the glottis nor the tract are stepped.
+    pub fn skip(&mut self, duration: Duration) {
+        let mut remaining = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        while remaining > 0 {
+            let len = remaining.min(self.block_len);
+            self.calculate_new_block_parameters(len as f32 / self.sample_rate as f32);
+            let glottis = self.glottis_mut();
+            glottis.skip(len);
+            let time = glottis.sample_count as f64 / self.sample_rate as f64;
+            self.tract_mut().skip_to(time);
+            self.startup_fade.position = self.startup_fade.position.saturating_add(len);
+            remaining -= len;
+        }
+    }
+
+    /// Synthesize `duration` worth of samples and discard them, so that
+    /// the following output starts from a settled state: past the voicing
+    /// attack, the tract relaxing from its initial shape, and the startup
+    /// of filters and gain control, which take a few hundred milliseconds.
+    /// Only a block-sized scratch buffer is allocated.
+    pub fn warmup(&mut self, duration: Duration) {
+        let mut remaining = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        let mut scratch = vec![0.0; self.block_len.min(remaining)];
+        while remaining > 0 {
+            let len = scratch.len().min(remaining);
+            self.synthesize(&mut scratch[..len]);
+            remaining -= len;
+        }
+    }
+
+    /// Synthesize `duration` worth of samples while gliding
+    /// `target_frequency` from `start_hz` to `end_hz`, at a constant rate
+    /// in semitones per second. `target_frequency` is updated every block,
+    /// and the actual frequency follows it with the usual smoothing.
+    /// Panics unless both frequencies are positive.
+    pub fn sweep(&mut self, start_hz: f32, end_hz: f32, duration: Duration) -> Vec<f32> {
+        if !(start_hz > 0.0 && end_hz > 0.0) {
+            panic!("sweep frequencies must be positive");
+        }
+        let len = (duration.as_secs_f64() * self.sample_rate as f64).round() as usize;
+        let mut buf = vec![0.0; len];
+        let mut offset = 0;
+        self.synthesize_with(&mut buf, |trombone| {
+            let progress = offset as f32 / len as f32;
+            trombone.set_target_frequency(start_hz * (end_hz / start_hz).powf(progress));
+            offset += trombone.block_size();
+        });
+        buf
+    }
+
+    /// Synthesize approximately `approx_duration` worth of samples,
+    /// that can be played in a loop without a click.
+    ///
+    /// The rest of the current glottal cycle is rendered and discarded,
+    /// then the result is trimmed to a whole number of glottal periods,
+    /// so the buffer starts and ends at a glottal cycle boundary.
+    /// The loop is only seamless if the frequency is steady,
+    /// e.g. vibrato is disabled and `target_frequency` was reached.
+    pub fn render_loopable(&mut self, approx_duration: Duration) -> Vec<f32> {
+        let sample_rate = self.sample_rate as f64;
+        let period = self.glottis().waveform_length as f64;
+        let lead_in = (period - self.glottis().time_in_waveform as f64).max(0.0);
+        let lead_in_len = (lead_in * sample_rate).round() as usize;
+        let periods = (approx_duration.as_secs_f64() / period).round().max(1.0);
+        let len = (periods * period * sample_rate).round() as usize;
+
+        let mut buf = vec![0.0; lead_in_len + len];
+        self.synthesize(&mut buf);
+        buf.drain(..lead_in_len);
+        buf
+    }
+
+    /// Whether a DC blocking filter removes slow offsets from the output,
+    /// before automatic gain control. The filter attenuates frequencies
+    /// below 40 Hz by more than 3 dB. Applies to `synthesize` and its
+    /// variants, except `synthesize_oversampled` and `synthesize_stereo`.
+    /// Disabled by default.
+    pub fn dc_blocker(&self) -> bool {
+        self.dc_blocker.is_some()
+    }
+    pub fn set_dc_blocker(&mut self, enabled: bool) {
+        if enabled != self.dc_blocker.is_some() {
+            self.dc_blocker = if enabled {
+                Some(DcBlocker::new(self.sample_rate))
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Gain applied to the output of `synthesize` and all its variants,
+    /// on top of the built-in scaling of the tract output. Default is 1.
+    /// NaN and infinite gains are ignored.
+    pub fn output_gain(&self) -> f32 {
+        self.output_gain
+    }
+    pub fn set_output_gain(&mut self, output_gain: f32) {
+        if output_gain.is_finite() {
+            self.output_gain = output_gain
+        }
+    }
+
+    /// Output gain that would bring the peak level of the next `probe`
+    /// worth of output to just below full scale (0.99), for use with
+    /// `set_output_gain`.
+    ///
+    /// The probe is rendered on a copy of the synthesizer with the current
+    /// settings and an output gain of 1, so this synthesizer's state,
+    /// including time and noise, is left unchanged. The gain only holds
+    /// for output resembling the probe: a louder vowel or a louder voice
+    /// later on can still clip. Returns 1 if the probe is silent.
+    pub fn calibrate_gain(&self, probe: Duration) -> f32 {
+        const FULL_SCALE: f32 = 0.99;
+        let mut copy = self.probe();
+        copy.output_gain = 1.0;
+        copy.glottal_closures = None;
+        let peak = copy
+            .render(probe)
+            .iter()
+            .fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        if peak > 0.0 {
+            FULL_SCALE / peak
+        } else {
+            1.0
+        }
+    }
+
+    /// Automatic gain control settings, `None` when disabled.
+    pub fn agc(&self) -> Option<AgcSettings> {
+        self.agc.as_ref().map(Agc::settings)
+    }
+    /// Enables automatic gain control, continuously adjusting the output
+    /// gain so that its RMS level approaches `target_rms`, or disables it
+    /// with `None` (the default). The level is tracked with the attack time
+    /// when it rises and the release time when it falls; short times
+    /// react quickly, but distort the waveform. With an attack shorter than
+    /// the release, the level follows the peaks of the signal power, so
+    /// voiced output settles somewhat below `target_rms`.
+    /// The gain is limited to 20dB.
+    /// Applies to `synthesize` and its variants, except
+    /// `synthesize_oversampled` and `synthesize_stereo`.
+    /// Changing the settings resets the gain to 1.
+    /// Panics unless `target_rms` is positive and finite.
+    pub fn set_agc(&mut self, settings: Option<AgcSettings>) {
+        self.agc = settings.map(|settings| Agc::new(settings, self.sample_rate));
+    }
+
+    /// Length of the linear fade-in applied to the output right after
+    /// construction or `reset`, hiding the click of the first glottal pulse.
+    pub fn startup_fade(&self) -> Duration {
+        Duration::from_secs_f64(self.startup_fade.len as f64 / self.sample_rate as f64)
+    }
+    /// Defaults to 0 (no fade). Only affects output not synthesized yet:
+    /// once `fade` has passed since construction or `reset`,
+    /// this has no effect until the next `reset`.
+    pub fn set_startup_fade(&mut self, fade: Duration) {
+        self.startup_fade.len = (fade.as_secs_f64() * self.sample_rate as f64).round() as usize;
+    }
+
+    /// Applies the current targets immediately, and starts the startup
+    /// fade (see `set_startup_fade`) over.
+    pub fn reset(&mut self) {
+        self.calculate_new_block_parameters(0.0);
+        self.startup_fade.position = 0;
+    }
+
+    /// Same as `synthesize`, but passes the `count` samples to `out` with
+    /// their index, so that they can be converted without a scratch buffer.
+    fn synthesize_each(&mut self, count: usize, mut out: impl FnMut(usize, f32)) {
+        if count == 0 {
+            return;
+        }
+        self.begin_call();
+        let mut p = 0;
+        while p < count {
+            let block_len = (count - p).min(self.block_len);
+            self.synthesize_block_with(block_len, Excitation::Glottis, |i, x| out(p + i, x));
+            p += block_len;
+        }
+    }
+
+    /// Returns the peak magnitude of the tract samples before decimation,
+    /// scaled like the output but without DC blocking and gain control.
+    fn synthesize_block(&mut self, buf: &mut [f32], excitation: Excitation) -> f32 {
+        self.synthesize_block_with(buf.len(), excitation, |i, x| buf[i] = x)
+    }
+
+    /// Same as `synthesize_block`, but passes the `len` samples to `out`.
+    fn synthesize_block_with(
+        &mut self,
+        len: usize,
+        excitation: Excitation,
+        mut out: impl FnMut(usize, f32),
+    ) -> f32 {
+        let mut decimator = std::mem::take(&mut self.decimator);
+        let mut agc = self.agc.take();
+        let mut dc_blocker = self.dc_blocker.take();
+        let fade = self.startup_fade;
+        let output_gain = self.output_gain;
+        let mut peak = 0.0_f32;
+        self.step_block(len, excitation, |i, s1, s2| {
+            let gain = 0.25 * fade.gain(i) * output_gain.abs();
+            peak = peak.max(s1.vocal.abs().max(s2.vocal.abs()) * gain);
+            let mut sample = decimator.decimate(s1.vocal, s2.vocal) * 0.25;
+            if let Some(dc_blocker) = &mut dc_blocker {
+                sample = dc_blocker.filter(sample as f64) as f32;
+            }
+            if let Some(agc) = &mut agc {
+                sample = agc.process(sample);
+            }
+            out(i, sample * fade.gain(i) * output_gain);
+        });
+        self.decimator = decimator;
+        self.agc = agc;
+        self.dc_blocker = dc_blocker;
+        peak
+    }
+
+    fn synthesize_oversampled_block(&mut self, buf: &mut [f32]) {
+        let fade = self.startup_fade;
+        let output_gain = self.output_gain;
+        self.step_block(buf.len() / 2, Excitation::Glottis, |i, s1, s2| {
+            let gain = 0.25 * fade.gain(i) * output_gain;
+            buf[2 * i] = s1.vocal * gain;
+            buf[2 * i + 1] = s2.vocal * gain;
+        });
+    }
+
+    /// Advances the synthesizer by `len` output samples,
+    /// passing both tract samples computed for each of them to `out`.
+    fn step_block<F: FnMut(usize, TractSample, TractSample)>(
+        &mut self,
+        len: usize,
+        excitation: Excitation,
+        mut out: F,
+    ) {
+        // an empty block would interpolate with NaN lambdas
+        if len == 0 {
+            return;
+        }
+        let delta_time = len as f32 / self.sample_rate as f32;
+        if let Excitation::Unison = excitation {
+            self.unison
+                .adjust_parameters(&self.shaper.tract.glottis, delta_time);
+        }
+        self.calculate_new_block_parameters(delta_time);
+        for i in 0..len {
+            let lambda1 = i as f64 / len as f64;
+            let lambda2 = (i as f64 + 0.5) / len as f64;
+            let glottal_output = match excitation {
+                Excitation::Glottis => self.glottal_sample(i, lambda1, false),
+                Excitation::Unison => self.glottal_sample(i, lambda1, true),
+                Excitation::Source(source) => source.get(i).copied().unwrap_or(0.0) as f64,
+            };
+            let sample1 = self.tract_sample(glottal_output, lambda1);
+            let sample2 = self.tract_sample(glottal_output, lambda2);
+            out(i, sample1, sample2);
+        }
+        self.call_offset += len;
+        self.startup_fade.position = self.startup_fade.position.saturating_add(len);
+    }
+
+    /// Tract samples per sample at twice the output rate.
+    fn tract_resampling_ratio(&self) -> f64 {
+        self.tract_sample_rate() as f64 / (2 * self.sample_rate) as f64
+    }
+
+    /// Next tract sample at twice the output rate.
+    fn tract_sample(&mut self, glottal_output: f64, lambda: f64) -> TractSample {
+        let ratio = self.tract_resampling_ratio();
+        if ratio == 1.0 {
+            return self.tract_step(glottal_output, lambda);
+        }
+        let mut resampler = self.tract_resampler;
+        resampler.position += ratio;
+        while resampler.position > 1.0 {
+            resampler.previous = resampler.current;
+            resampler.current = self.tract_step(glottal_output, lambda);
+            resampler.position -= 1.0;
+        }
+        self.tract_resampler = resampler;
+        TractSample::interpolate(
+            resampler.previous,
+            resampler.current,
+            resampler.position as f32,
+        )
+    }
+
+    fn tract_step(&mut self, glottal_output: f64, lambda: f64) -> TractSample {
+        let tract = self.tract_mut();
+        let vocal = tract.step(glottal_output, lambda);
+        TractSample {
+            vocal,
+            lip: tract.last_lip_output as f32,
+            nose: tract.last_nose_output as f32,
+        }
+    }
+
+    fn glottal_sample(&mut self, i: usize, lambda: f64, unison: bool) -> f64 {
+        let (pulse, aspiration) = self.glottis_mut().step_components(lambda as f32);
+        if let Some(closures) = &mut self.glottal_closures {
+            if self.shaper.tract.glottis.cycle_started {
+                closures.push(self.call_offset + i);
+            }
+        }
+        let mut pulse = pulse as f64;
+        if unison {
+            // the voices are averaged, while aspiration only comes
+            // from the main glottis and keeps its level
+            let voices = self.unison.step(lambda as f32) as f64;
+            pulse = (pulse + voices) / self.unison.len() as f64;
+        }
+        let glottal_output = if self.glottal_source_enabled {
+            pulse + aspiration as f64
+        } else {
+            aspiration as f64
+        };
+        self.spectral_tilt.filter(glottal_output)
+    }
+
+    /// A copy to synthesize on without affecting this synthesizer,
+    /// leaving out the RNG kept by `with_owned_rng`.
+    fn probe(&self) -> PinkTrombone {
+        PinkTrombone {
+            owned_rng: None,
+            ..self.clone()
+        }
+    }
+
+    fn begin_call(&mut self) {
+        self.call_offset = 0;
+        self.sample_buffer_pos = self.sample_buffer.len();
+        if let Some(closures) = &mut self.glottal_closures {
+            closures.clear();
+        }
+    }
+
+    fn calculate_new_block_parameters(&mut self, delta_time: f32) {
+        self.glottis_mut().adjust_parameters(delta_time);
+        self.shaper.adjust_tract_shape(delta_time as f64);
+        self.tract_mut()
+            .calculate_new_block_parameters(delta_time as f64);
+    }
+
+    fn tract(&self) -> &Tract {
+        &self.shaper.tract
+    }
+
+    fn glottis(&self) -> &Glottis {
+        &self.tract().glottis
+    }
+
+    fn glottis_mut(&mut self) -> &mut Glottis {
+        &mut self.tract_mut().glottis
+    }
+
+    fn tract_mut(&mut self) -> &mut Tract {
+        &mut self.shaper.tract
+    }
+}
+
+/// Frequency of the sine with the same ratio of first difference
+/// to full-band energy as `samples`, whose difference filter has
+/// a power gain of `4 sin²(πf/sample_rate)`.
+fn brightness_centroid(samples: &[f32], sample_rate: u32) -> f32 {
+    let (mut full, mut high) = (0.0, 0.0);
+    for pair in samples.windows(2) {
+        full += math::sqr(pair[1] as f64);
+        high += math::sqr((pair[1] - pair[0]) as f64);
+    }
+    if full == 0.0 {
+        return 0.0;
+    }
+    let ratio = (high / full / 4.0).sqrt().min(1.0);
+    (ratio.asin() * sample_rate as f64 / std::f64::consts::PI) as f32
+}
+
+/// Closed polygon around a tube with the given cell `diameters`,
+/// the first cell at `start`.
+fn outline(start: usize, diameters: &[f64]) -> Vec<(f32, f32)> {
+    let wall =
+        |sign: f64| move |(i, d): (usize, &f64)| ((start + i) as f32, (sign * d / 2.0) as f32);
+    let upper = diameters.iter().enumerate().map(wall(1.0));
+    let lower = diameters.iter().enumerate().rev().map(wall(-1.0));
+    upper.chain(lower).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        glottal_source::SourceParams,
+        rng::xorshift,
+        testing::{self, ConstantNoise, SAMPLE_RATE, SEED},
+    };
+
+    #[test]
+    fn reproducible() {
+        let mut random = xorshift::XorShift128::new(SEED.into());
+        let mut trombone = PinkTrombone::new(SAMPLE_RATE, &mut random, SEED);
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize * 15];
+        trombone.synthesize(&mut buffer);
+        assert_eq!(format!("{:.10}", buffer.last().unwrap()), "0.0383924171");
+    }
+
+    #[test]
+    fn glottal_closures_follow_frequency() {
+        let mut trombone = testing::trombone();
+        trombone.set_track_glottal_closures(true);
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize];
+        trombone.synthesize(&mut buffer);
+        let closures = trombone.take_glottal_closures();
+        assert!(closures.windows(2).all(|w| w[0] < w[1]));
+        assert!(*closures.last().unwrap() < buffer.len());
+        let period = SAMPLE_RATE as f32 / trombone.target_frequency();
+        let mean_period =
+            (closures.last().unwrap() - closures[0]) as f32 / (closures.len() - 1) as f32;
+        assert!((mean_period / period - 1.0).abs() < 0.2);
+        assert!(trombone.take_glottal_closures().is_empty());
+    }
+
+    #[test]
+    fn impulse_response_leaves_state_unchanged() {
+        let render = |probe: bool| {
+            let mut trombone = testing::trombone();
+            let mut buffer = vec![0.0; SAMPLE_RATE as usize / 2];
+            trombone.synthesize(&mut buffer[..SAMPLE_RATE as usize / 4]);
+            if probe {
+                let response = trombone.impulse_response(1024);
+                assert!(response.iter().any(|&x| x != 0.0));
+                assert!(response.iter().all(|x| x.is_finite()));
+            }
+            trombone.synthesize(&mut buffer[SAMPLE_RATE as usize / 4..]);
+            buffer
+        };
+        assert_eq!(render(false), render(true));
+    }
+
+    #[test]
+    fn nose_length_changes() {
+        let mut trombone = testing::trombone();
+        trombone.set_velum_open(true);
+        trombone.set_nose_length(36);
+        assert_eq!(trombone.nose_profile().len(), 36);
+        let mut profile = trombone.nose_profile().to_vec();
+        profile.truncate(20);
+        trombone.set_nose_profile(&profile);
+        assert_eq!(trombone.nose_profile()[1..], profile[1..]);
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 4];
+        trombone.synthesize(&mut buffer);
+        assert!(buffer.iter().all(|x| x.is_finite()));
+        assert!(trombone.nose_output() != 0.0);
+    }
+
+    #[test]
+    fn finite_output_across_tenseness() {
+        let mut trombone = testing::trombone();
+        let mut buffer = vec![0.0; 1024];
+        for i in 0..=200 {
+            trombone.set_target_tenseness(i as f32 / 200.0);
+            trombone
+                .synthesize_checked(&mut buffer)
+                .expect("output must stay finite");
+        }
+    }
+
+    #[test]
+    fn silent_after_note_off() {
+        let mut trombone = testing::trombone();
+        trombone.note_on(60, 100);
+        trombone.render(Duration::from_millis(500));
+        assert!(!trombone.is_silent(1e-3));
+        trombone.note_off();
+        let mut silent_after = None;
+        for block in 0..300 {
+            trombone.render(Duration::from_millis(10));
+            if trombone.is_silent(1e-3) {
+                silent_after = Some(block);
+                break;
+            }
+        }
+        assert!(silent_after.is_some());
+    }
+
+    #[test]
+    fn plosive_bursts_on_release() {
+        let mut trombone = testing::trombone();
+        trombone.render(Duration::from_millis(200));
+        trombone.plosive(
+            crate::landmarks::TIP_START + 3,
+            Duration::from_millis(100),
+            Duration::from_millis(50),
+        );
+        let mut burst = false;
+        for _ in 0..40 {
+            trombone.render(Duration::from_millis(10));
+            burst |= !trombone.tract().transients.is_empty();
+        }
+        assert!(burst);
+    }
+
+    #[test]
+    fn sweep_tracks_frequency() {
+        let mut trombone = testing::trombone();
+        trombone.set_vibrato_enabled(false);
+        trombone.set_track_glottal_closures(true);
+        let (start_hz, end_hz, duration) = (100.0, 300.0, 2.0);
+        let buffer = trombone.sweep(start_hz, end_hz, Duration::from_secs_f32(duration));
+        assert_eq!(buffer.len(), (SAMPLE_RATE as f32 * duration) as usize);
+        let closures = trombone.take_glottal_closures();
+        let mut checked = 0;
+        for pair in closures.windows(2) {
+            let time = (pair[0] + pair[1]) as f32 / 2.0 / SAMPLE_RATE as f32;
+            // skip the glide from the default frequency
+            if time < 0.2 {
+                continue;
+            }
+            let frequency = SAMPLE_RATE as f32 / (pair[1] - pair[0]) as f32;
+            let expected = start_hz * (end_hz / start_hz).powf(time / duration);
+            assert!(
+                (frequency / expected - 1.0).abs() < 0.1,
+                "{} Hz at {}s, expected {} Hz",
+                frequency,
+                time,
+                expected
+            );
+            checked += 1;
+        }
+        assert!(checked > 200);
+    }
+
+    #[test]
+    fn match_formants_finds_vowel() {
+        let mut trombone = testing::trombone();
+        let mut target = trombone.clone();
+        target.set_tongue_vowel(0.2, 0.3);
+        target.shaper.snap_to_target();
+        let formants = target.estimate_formants();
+        let (f1, f2) = (formants[0], formants[1]);
+
+        trombone.set_tongue_vowel(0.7, 0.7);
+        let (m1, m2) = trombone.match_formants(f1, f2, 30);
+        assert!((m1 / f1 - 1.0).abs() < 0.05, "F1 {} instead of {}", m1, f1);
+        assert!((m2 / f2 - 1.0).abs() < 0.05, "F2 {} instead of {}", m2, f2);
+    }
+
+    #[test]
+    fn frozen_pitch_is_steady() {
+        let mut trombone = testing::trombone();
+        trombone.set_frozen(true);
+        trombone.set_track_glottal_closures(true);
+        trombone.set_musical_note(0.0);
+        let period = SAMPLE_RATE as f32 / math::semitone_to_hz(0.0);
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize];
+        trombone.synthesize(&mut buffer);
+        let closures = trombone.take_glottal_closures();
+        assert!(closures.len() > 100);
+        for pair in closures.windows(2) {
+            assert!(((pair[1] - pair[0]) as f32 - period).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn time_stays_accurate_in_long_renders() {
+        let mut trombone = testing::trombone();
+        // skip ahead to where single precision time steps by several samples
+        let start = 1 << 26;
+        trombone.glottis_mut().sample_count = start;
+        trombone.tract_mut().sample_count = 2 * start;
+        let block = SAMPLE_RATE as usize / 100;
+        let mut buffer = vec![0.0; block];
+        for k in 1..=100 {
+            trombone.synthesize(&mut buffer);
+            assert!(buffer.iter().all(|x| x.is_finite()));
+            let expected = start + (k * block) as u64;
+            let time = trombone.time().as_secs_f64();
+            assert!((time - expected as f64 / SAMPLE_RATE as f64).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn disabled_glottal_source_whispers() {
+        let render = |enabled: bool| {
+            let mut trombone = testing::trombone();
+            trombone.set_target_tenseness(0.2);
+            trombone.set_glottal_source_enabled(enabled);
+            let mut buffer = vec![0.0; SAMPLE_RATE as usize / 2];
+            trombone.synthesize(&mut buffer);
+            let energy: f32 = buffer.iter().map(|x| x * x).sum();
+            (energy / buffer.len() as f32).sqrt()
+        };
+        let voiced = render(true);
+        let whispered = render(false);
+        assert!(whispered > 0.0);
+        assert!(whispered < voiced * 0.5, "{} vs {}", whispered, voiced);
+    }
+
+    #[test]
+    fn reseed_is_deterministic() {
+        let mut trombone = testing::trombone();
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 4];
+        trombone.synthesize(&mut buffer);
+        let render = |seed: Option<u64>| {
+            let mut trombone = trombone.clone();
+            if let Some(seed) = seed {
+                trombone.reseed(seed);
+            }
+            let mut buffer = vec![0.0; SAMPLE_RATE as usize / 4];
+            trombone.synthesize(&mut buffer);
+            buffer
+        };
+        let reseeded = render(Some(7));
+        assert_eq!(reseeded, render(Some(7)));
+        assert_ne!(reseeded, render(Some(8)));
+        assert_ne!(reseeded, render(None));
+    }
+
+    #[test]
+    fn say_renders_all_gestures() {
+        let mut trombone = testing::trombone();
+        let ms = Duration::from_millis;
+        let gestures = [
+            Gesture::Silence(ms(100)),
+            Gesture::Fricative {
+                position: 36.0,
+                diameter: 0.4,
+                duration: ms(150),
+                voiced: false,
+            },
+            Gesture::Vowel(Phoneme::A, ms(200)),
+            Gesture::Plosive {
+                position: Tract::TIP_START + 2,
+                closure: ms(60),
+                release: ms(30),
+                voiced: false,
+            },
+            Gesture::Vowel(Phoneme::I, ms(200)),
+            Gesture::Silence(ms(400)),
+        ];
+        let mut out = Vec::new();
+        trombone.say(&gestures, &mut out);
+        let duration: Duration = gestures.iter().map(Gesture::duration).sum();
+        let expected = duration.as_secs_f64() * SAMPLE_RATE as f64;
+        assert!((out.len() as f64 - expected).abs() <= gestures.len() as f64);
+        assert!(out.iter().all(|x| x.is_finite()));
+        assert!(trombone.always_voice());
+
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        let at = |ms: usize| ms * SAMPLE_RATE as usize / 1000;
+        // the fricative hisses, the vowel is louder, the final silence fades out
+        let fricative = rms(&out[at(150)..at(250)]);
+        let vowel = rms(&out[at(300)..at(450)]);
+        let silence = rms(&out[out.len() - at(50)..]);
+        assert!(fricative > silence * 2.0, "{} {}", fricative, silence);
+        assert!(vowel > fricative, "{} {}", vowel, fricative);
+    }
+
+    #[test]
+    fn sound_speed_scales_formants() {
+        let mut trombone = testing::trombone();
+        trombone.set_phoneme(Phoneme::A);
+        trombone.shaper.snap_to_target();
+        let length = trombone.tract_length_cm();
+        let air = trombone.estimate_formants();
+
+        let ratio = 1.5;
+        trombone.set_sound_speed(trombone.sound_speed() * ratio);
+        assert!((trombone.tract_length_cm() / length - 1.0).abs() < 1e-3);
+        let fast = trombone.estimate_formants();
+        for k in 0..2 {
+            assert!(
+                (fast[k] / air[k] / ratio - 1.0).abs() < 0.05,
+                "{:?} {:?}",
+                air,
+                fast
+            );
+        }
+
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 2];
+        trombone.synthesize(&mut buffer);
+        assert!(buffer.iter().all(|x| x.is_finite()));
+        assert!(buffer.iter().any(|&x| x != 0.0));
+        let time = trombone.time().as_secs_f64();
+        assert!((time - 0.5).abs() < 1e-3, "{}", time);
+    }
+
+    #[test]
+    fn velum_settles() {
+        let mut trombone = testing::trombone();
+        trombone.render(Duration::from_millis(500));
+        assert!(trombone.velum_settled());
+        trombone.set_velum_open(true);
+        trombone.render(Duration::from_millis(20));
+        assert!(!trombone.velum_settled());
+        trombone.render(Duration::from_millis(500));
+        assert!(trombone.velum_settled());
+        assert_eq!(trombone.velum_opening(), trombone.velum_target());
+    }
+
+    #[test]
+    fn brightness_centroid_finds_tones() {
+        for &frequency in &[200.0, 1000.0, 5000.0] {
+            let tone: Vec<f32> = (0..SAMPLE_RATE / 10)
+                .map(|n| {
+                    let phase = 2.0 * std::f64::consts::PI * frequency * n as f64;
+                    (phase / SAMPLE_RATE as f64).sin() as f32
+                })
+                .collect();
+            let centroid = brightness_centroid(&tone, SAMPLE_RATE);
+            assert!(
+                (centroid / frequency as f32 - 1.0).abs() < 0.01,
+                "{}",
+                centroid
+            );
+        }
+        assert_eq!(brightness_centroid(&[0.0; 100], SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn envelope_follows_output() {
+        let mut trombone = testing::trombone();
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 2];
+        let mut envelope = vec![0.0; buffer.len() - 1];
+        assert_eq!(
+            trombone.synthesize_with_envelope(&mut buffer, &mut envelope),
+            Err(LengthMismatch {
+                expected: buffer.len(),
+                actual: envelope.len()
+            })
+        );
+        envelope.push(0.0);
+        trombone
+            .synthesize_with_envelope(&mut buffer, &mut envelope)
+            .unwrap();
+        let mean =
+            |samples: &[f32]| samples.iter().map(|x| x.abs()).sum::<f32>() / samples.len() as f32;
+        let tail = buffer.len() / 2..;
+        let level = mean(&envelope[tail.clone()]);
+        assert!((level / mean(&buffer[tail]) - 1.0).abs() < 0.2);
+        assert!(envelope.iter().all(|&x| x >= 0.0));
+    }
+
+    #[test]
+    fn custom_glottal_source_replaces_pulses() {
+        #[derive(Clone)]
+        struct Silent;
+        impl GlottalSource for Silent {
+            fn sample(&mut self, _phase: f32, _params: &SourceParams) -> f32 {
+                0.0
+            }
+        }
+
+        let mut trombone = testing::trombone();
+        // no aspiration noise at full tenseness
+        trombone.glottis_mut().set_target_tenseness(1.0);
+        let mut voiced = trombone.clone();
+        trombone.set_glottal_source(Box::new(Silent));
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize / 10];
+        voiced.synthesize(&mut buffer);
+        assert!(buffer.iter().any(|x| x.abs() > 0.01));
+        trombone.synthesize(&mut buffer);
+        assert!(buffer.iter().all(|x| x.abs() < 1e-6));
+    }
+
+    #[test]
+    fn natural_onset_grows_pulses() {
+        let mut abrupt = testing::trombone();
+        abrupt.set_always_voice(false);
+        abrupt.render(Duration::from_millis(100));
+        let mut natural = abrupt.clone();
+        natural.set_natural_onset(true);
+
+        let peak = |trombone: &mut PinkTrombone| {
+            trombone.set_touched(true);
+            let mut buffer = vec![0.0; SAMPLE_RATE as usize / 40];
+            trombone.synthesize(&mut buffer);
+            buffer.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()))
+        };
+        let abrupt_peak = peak(&mut abrupt);
+        let natural_peak = peak(&mut natural);
+        assert!(natural_peak < abrupt_peak * 0.9);
+    }
+
+    #[test]
+    fn set_loudness_persists() {
+        let mut trombone = testing::trombone();
+        trombone.set_loudness(0.3);
+        // dozens of glottal cycles
+        trombone.render(Duration::from_millis(300));
+        assert_eq!(trombone.loudness(), 0.3);
+        assert_eq!(trombone.loudness_mode(), LoudnessMode::Manual);
+
+        trombone.set_loudness_mode(LoudnessMode::Fixed(0.5));
+        assert_eq!(trombone.loudness(), 0.5);
+        trombone.render(Duration::from_millis(50));
+        assert_eq!(trombone.loudness(), 0.5);
+
+        trombone.set_loudness_mode(LoudnessMode::FollowTenseness);
+        trombone.render(Duration::from_millis(50));
+        let tenseness = trombone.glottis().current_tenseness();
+        assert!((trombone.loudness() - tenseness.powf(0.25)).abs() < 0.1);
+    }
+
+    #[test]
+    fn ring_buffer_wraps_around() {
+        let mut trombone = testing::trombone();
+        trombone.render(Duration::from_millis(100));
+        let mut ring = vec![0.0; 1000];
+        let mut write_pos = 900;
+        trombone.synthesize_into_ring(&mut ring, &mut write_pos, 150);
+        assert_eq!(write_pos, 50);
+        assert!(ring[100..900].iter().all(|&x| x == 0.0));
+        assert!(ring[..50].iter().chain(&ring[900..]).any(|&x| x != 0.0));
+
+        let before = trombone.time();
+        trombone.synthesize_into_ring(&mut ring, &mut write_pos, 2500);
+        assert_eq!(write_pos, 550);
+        let rendered = (trombone.time() - before).as_secs_f64() * SAMPLE_RATE as f64;
+        assert!((rendered - 2500.0).abs() < 1e-3);
+        assert!(ring.iter().filter(|&&x| x == 0.0).count() < 10);
+    }
+
+    /// Amplitude of the `hz` component of `signal`.
+    fn magnitude_at(signal: &[f32], hz: f64) -> f64 {
+        let freq = hz / SAMPLE_RATE as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (n, &x) in signal.iter().enumerate() {
+            let phase = 2.0 * std::f64::consts::PI * freq * n as f64;
+            re += x as f64 * phase.cos();
+            im -= x as f64 * phase.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+    }
+
+    /// A synthesizer with aspiration and frication noise silenced.
+    fn noiseless() -> PinkTrombone {
+        PinkTrombone::with_noise_sources(
+            SAMPLE_RATE,
+            &mut ConstantNoise(0.5),
+            &mut ConstantNoise(0.5),
+            SEED,
+            NoiseMode::default(),
+        )
+    }
+
+    #[test]
+    fn constant_noise_silences_noise() {
+        let mut trombone = noiseless();
+        trombone.set_glottal_source_enabled(false);
+        trombone.glottis_mut().set_target_tenseness(0.0);
+        let output = trombone.render(Duration::from_millis(100));
+        assert!(output.iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn pre_aspiration_replaces_voicing() {
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        let mut trombone = testing::trombone();
+        trombone.render(Duration::from_millis(200));
+        let mut voiceless = noiseless();
+        voiceless.render(Duration::from_millis(200));
+
+        trombone.pre_aspirate(Duration::from_millis(100));
+        voiceless.pre_aspirate(Duration::from_millis(100));
+        let breath = trombone.render(Duration::from_millis(100));
+        let silence = voiceless.render(Duration::from_millis(100));
+        // the tract rings out during the first half
+        assert!(rms(&breath[2400..]) > 1e-3);
+        assert!(rms(&silence[2400..]) < 1e-4);
+
+        let voiced = voiceless.render(Duration::from_millis(50));
+        assert!(rms(&voiced) > 0.01);
+    }
+
+    #[test]
+    fn tenseness_steady_without_drift() {
+        let mut trombone = testing::trombone();
+        trombone.set_tenseness_drift(false);
+        trombone.set_target_tenseness(0.7);
+        // past the attack
+        trombone.render(Duration::from_millis(200));
+        for _ in 0..50 {
+            trombone.render(Duration::from_millis(20));
+            assert_eq!(trombone.glottis().current_tenseness(), 0.7);
+        }
+    }
+
+    #[test]
+    fn advance_keeps_fractional_samples() {
+        let mut trombone = testing::trombone();
+        let mut out = Vec::new();
+        // just under 16 samples each
+        for _ in 0..300 {
+            trombone.advance(Duration::from_nanos(333_333), &mut out);
+        }
+        // 0.0999999 s
+        assert_eq!(out.len(), 4799);
+        trombone.advance(Duration::from_nanos(100), &mut out);
+        assert_eq!(out.len(), 4800);
+        assert!(out.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn morph_reaches_target_config() {
+        let mut trombone = testing::trombone();
+        let start = trombone.config();
+        let target = PinkTromboneConfig {
+            tongue_index: 30.0,
+            tongue_diameter: 2.0,
+            velum_open: true,
+            area_function: None,
+        };
+        trombone.set_morph_curve(MorphCurve::Cosine);
+        trombone.morph_to(&target, Duration::from_millis(200));
+        trombone.render(Duration::from_millis(100));
+        let halfway = trombone.tongue_index();
+        assert!(halfway > start.tongue_index && halfway < target.tongue_index);
+        trombone.render(Duration::from_millis(150));
+        assert_eq!(trombone.config(), target);
+
+        let mut areas = vec![1.0; 10];
+        areas[7] = 0.1;
+        let target = PinkTromboneConfig {
+            area_function: Some(areas),
+            ..target
+        };
+        trombone.morph_to(&target, Duration::ZERO);
+        trombone.render(Duration::from_millis(10));
+        let config = trombone.config();
+        assert_eq!(config.area_function.as_ref().unwrap().len(), Tract::N);
+    }
+
+    #[test]
+    fn setters_ignore_nan() {
+        let mut trombone = testing::trombone();
+        let before = (
+            trombone.intensity(),
+            trombone.target_frequency(),
+            trombone.target_tenseness(),
+            trombone.tongue_index(),
+            trombone.tongue_diameter(),
+            trombone.vibrato_gain(),
+            trombone.vibrato_frequency(),
+            trombone.articulation_speed(),
+            trombone.spectral_tilt(),
+            trombone.output_gain(),
+        );
+        let more_before = (
+            trombone.sound_speed(),
+            trombone.max_frequency(),
+            trombone.glottal_phase(),
+        );
+        trombone.set_intensity(f32::NAN);
+        trombone.set_loudness(f32::NAN);
+        trombone.set_loudness_mode(LoudnessMode::Fixed(f32::NAN));
+        trombone.set_target_frequency(f32::NAN);
+        trombone.set_musical_note(f32::NAN);
+        trombone.set_target_tenseness(f32::NAN);
+        trombone.set_voice(f32::NAN, f32::NAN, f32::NAN);
+        trombone.set_tongue_index(f64::NAN);
+        trombone.set_tongue_diameter(f64::NAN);
+        trombone.set_tongue_vowel(f32::NAN, f32::NAN);
+        trombone.set_tongue_body_speed(f64::NAN);
+        trombone.set_tongue_tip_speed(f64::NAN);
+        trombone.set_constriction(Some(Constriction {
+            index: f64::NAN,
+            diameter: f64::NAN,
+        }));
+        trombone.set_articulation_speed(f64::NAN);
+        trombone.set_spectral_tilt(f32::NAN);
+        trombone.set_vibrato_gain(f32::NAN);
+        trombone.set_vibrato_frequency(f32::NAN);
+        trombone.set_aspiration_modulation(f32::NAN);
+        trombone.set_output_gain(f32::NAN);
+        trombone.set_sound_speed(f32::NAN);
+        trombone.set_max_frequency(f32::NAN);
+        trombone.set_glottal_phase(f32::NAN);
+        trombone.set_glottal_phase(f32::INFINITY);
+        trombone.set_params_pending(Params {
+            target_frequency: Some(f32::NAN),
+            tongue_index: Some(f64::NAN),
+            ..Params::default()
+        });
+
+        let after = (
+            trombone.intensity(),
+            trombone.target_frequency(),
+            trombone.target_tenseness(),
+            trombone.tongue_index(),
+            trombone.tongue_diameter(),
+            trombone.vibrato_gain(),
+            trombone.vibrato_frequency(),
+            trombone.articulation_speed(),
+            trombone.spectral_tilt(),
+            trombone.output_gain(),
+        );
+        assert_eq!(after, before);
+        let more_after = (
+            trombone.sound_speed(),
+            trombone.max_frequency(),
+            trombone.glottal_phase(),
+        );
+        assert_eq!(more_after, more_before);
+        assert_eq!(trombone.loudness_mode(), LoudnessMode::FollowTenseness);
+        assert_eq!(trombone.constriction(), None);
+        assert_eq!(trombone.tongue_body_speed(), 0.6);
+        assert_eq!(trombone.aspiration_modulation(), 1.0);
+
+        let mut buffer = vec![0.0; 4800];
+        trombone.fill(&mut buffer);
+        assert!(buffer.iter().all(|x| x.is_finite()));
+        assert!(buffer.iter().any(|&x| x != 0.0));
+    }
+
+    #[test]
+    fn setters_clamp_to_range() {
+        let mut trombone = testing::trombone();
+        trombone.set_voice(f32::INFINITY, 2.0, -1.0);
+        assert_eq!(trombone.target_frequency(), f32::MAX);
+        assert_eq!(trombone.target_tenseness(), 1.0);
+        assert_eq!(trombone.intensity(), 0.0);
+        trombone.set_target_frequency(-100.0);
+        assert_eq!(trombone.target_frequency(), 10.0);
+        trombone.set_tongue_index(100.0);
+        assert_eq!(trombone.tongue_index(), (Tract::N - 1) as f64);
+        trombone.set_tongue_diameter(-1.0);
+        assert_eq!(trombone.tongue_diameter(), 0.0);
+        trombone.set_loudness(1.5);
+        assert_eq!(trombone.loudness(), 1.0);
+        trombone.set_loudness_mode(LoudnessMode::Fixed(-1.0));
+        assert_eq!(trombone.loudness_mode(), LoudnessMode::Fixed(0.0));
+        trombone.set_vibrato_frequency(-6.0);
+        assert_eq!(trombone.vibrato_frequency(), 0.0);
+        trombone.set_max_frequency(0.0);
+        assert_eq!(trombone.max_frequency(), 10.0);
+        trombone.set_sound_speed(-343.0);
+        assert!(trombone.sound_speed() > 0.0);
+        assert_eq!(trombone.tract_sample_rate(), 1);
+    }
+
+    #[test]
+    fn frequency_ramp_is_sample_accurate() {
+        let mut trombone = testing::trombone();
+        trombone.set_vibrato_enabled(false);
+        let mut buffer = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+
+        trombone.set_track_glottal_closures(true);
+        trombone.ramp_frequency(280.0, 4800);
+        // within a single block
+        trombone.synthesize(&mut buffer[..100]);
+        let expected = 140.0 + 140.0 * 100.0 / 4800.0;
+        assert!((trombone.target_frequency() - expected).abs() < 1e-3);
+        trombone.synthesize(&mut buffer[100..]);
+        assert_eq!(trombone.target_frequency(), 280.0);
+
+        let closures = trombone.take_glottal_closures();
+        let periods: Vec<usize> = closures.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(periods.len() > 10);
+        // the pitch rises steadily, and not in steps at block boundaries
+        assert!(periods.windows(2).all(|w| w[1] <= w[0]), "{:?}", periods);
+        assert!(periods[0] >= 320 && *periods.last().unwrap() <= 180);
+
+        trombone.set_target_frequency(200.0);
+        trombone.synthesize(&mut buffer);
+        assert_eq!(trombone.target_frequency(), 200.0);
+    }
+
+    #[test]
+    fn glottal_wavetable_matches_pulse() {
+        let mut trombone = testing::trombone();
+        let mut buffer = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+
+        let normalized = trombone.normalized_glottal_wavetable(256);
+        assert_eq!(normalized.len(), 256);
+        let peak = normalized.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        assert!((peak - 1.0).abs() < 0.1, "{}", peak);
+        // the pulse ends with the return phase, back near zero
+        assert!(normalized[255].abs() < 0.1);
+
+        let scaled = trombone.glottal_wavetable(256);
+        let gain = trombone.intensity() * trombone.loudness();
+        for (x, y) in normalized.iter().zip(&scaled) {
+            assert!((x * gain - y).abs() < 1e-6);
+        }
+
+        // sampling the table does not change the synthesis
+        let mut reference = trombone.clone();
+        let mut expected = vec![0.0; 480];
+        reference.synthesize(&mut expected);
+        trombone.glottal_wavetable(1000);
+        trombone.synthesize(&mut buffer[..480]);
+        assert_eq!(buffer[..480], expected[..]);
+    }
+
+    #[test]
+    fn synthesize_to_sink_matches_synthesize() {
+        let mut trombone = testing::trombone();
+        let mut reference = trombone.clone();
+        let mut expected = vec![0.0; 2000];
+        reference.synthesize(&mut expected[..1200]);
+        reference.synthesize(&mut expected[1200..]);
+
+        let mut out = Vec::new();
+        trombone.synthesize_to(&mut out, 1200);
+        let mut buffer = vec![0.0; 1000];
+        let mut sink = &mut buffer[..];
+        trombone.synthesize_to(&mut sink, 800);
+        assert_eq!(sink.len(), 200);
+        out.extend_from_slice(&buffer[..800]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn turbulence_intensities_follow_envelope() {
+        let mut trombone = testing::trombone();
+        trombone.set_fricative_attack(Duration::from_millis(100));
+        trombone.set_fricative_release(Duration::from_millis(100));
+        let start_time = trombone.tract().time;
+        trombone.set_turbulence_points(vec![TurbulencePoint {
+            diameter: 0.5,
+            position: 30.0,
+            start_time,
+            end_time: f64::NAN,
+        }]);
+        let mut buffer = vec![0.0; 2400];
+        trombone.synthesize(&mut buffer);
+        let attack = trombone.turbulence_intensities()[0];
+        assert!((attack - 0.5).abs() < 0.01, "{}", attack);
+        trombone.synthesize(&mut buffer);
+        trombone.synthesize(&mut buffer);
+        assert_eq!(trombone.turbulence_intensities(), vec![1.0]);
+
+        let end_time = trombone.tract().time;
+        let mut points = trombone.turbulence_points().to_vec();
+        points[0].end_time = end_time;
+        trombone.set_turbulence_points(points);
+        trombone.synthesize(&mut buffer);
+        let release = trombone.turbulence_intensities()[0];
+        assert!((release - 0.5).abs() < 0.01, "{}", release);
+    }
+
+    #[test]
+    fn skip_keeps_modulation_continuous() {
+        let mut trombone = testing::trombone();
+        let mut rendered = trombone.clone();
+        let mut buffer = vec![0.0; SAMPLE_RATE as usize];
+        rendered.synthesize(&mut buffer);
+        trombone.skip(Duration::from_secs(1));
+
+        assert_eq!(trombone.time(), rendered.time());
+        assert_eq!(trombone.current_frequency(), rendered.current_frequency());
+        assert_eq!(trombone.current_tenseness(), rendered.current_tenseness());
+        assert_eq!(trombone.intensity(), rendered.intensity());
+        trombone.synthesize(&mut buffer[..4800]);
+        assert!(buffer[..4800].iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn next_sample_matches_synthesize() {
+        let mut trombone = testing::trombone();
+        trombone.set_block_size(300);
+        trombone.set_target_frequency(220.0);
+        trombone.set_phoneme(Phoneme::I);
+        let mut reference = trombone.clone();
+
+        // buffers of the block size recalculate block parameters
+        // at the same samples as next_sample does
+        let mut expected = vec![0.0; 12 * 300];
+        for block in expected.chunks_mut(300) {
+            reference.synthesize(block);
+        }
+        let actual: Vec<f32> = (0..expected.len())
+            .map(|_| trombone.next_sample())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn uniform_tract_spectrum_peaks_at_odd_harmonics() {
+        let mut trombone = testing::trombone();
+        trombone.set_frozen(true);
+        trombone.set_area_function(&[4.0; Tract::N]);
+        trombone.set_velum_open(false);
+        let mut buffer = vec![0.0; 512];
+        trombone.synthesize(&mut buffer);
+
+        // a quarter-wavelength resonator: peaks at odd multiples of
+        // the fundamental, troughs at even ones
+        let fundamental = trombone.tube_fundamental();
+        let frequencies: Vec<f32> = (1..=6).map(|k| k as f32 * fundamental).collect();
+        let spectrum = trombone.tract_spectrum(&frequencies);
+        for k in (0..6).step_by(2) {
+            assert!(spectrum[k] > spectrum[k + 1] + 10.0, "{:?}", spectrum);
+        }
+        let near = trombone.tract_spectrum(&[0.9 * fundamental, 1.1 * fundamental]);
+        assert!(near.iter().all(|&db| db < spectrum[0]), "{:?}", near);
+    }
+
+    #[test]
+    fn empty_and_single_sample_buffers() {
+        let mut trombone = testing::trombone();
+        let mut reference = trombone.clone();
+        trombone.synthesize(&mut []);
+        trombone.synthesize_oversampled(&mut []);
+        trombone.synthesize_preemphasis(&mut [], 0.97);
+        trombone.fill(&mut []);
+        let mut buffer = vec![0.0; 4800];
+        let mut expected = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+        reference.synthesize(&mut expected);
+        assert_eq!(buffer, expected);
+
+        for x in buffer.iter_mut() {
+            trombone.synthesize(std::slice::from_mut(x));
+        }
+        assert!(buffer.iter().all(|x| x.is_finite()));
+        assert!(buffer.iter().any(|&x| x.abs() > 0.01));
+    }
+
+    #[test]
+    fn detects_own_pitch() {
+        let mut trombone = testing::trombone();
+        trombone.set_vibrato_enabled(false);
+        trombone.set_target_frequency(180.0);
+        trombone.warmup(Duration::from_millis(500));
+        let output = trombone.render(Duration::from_millis(100));
+        let pitch = PinkTrombone::detect_pitch(&output, SAMPLE_RATE).unwrap();
+        assert!((pitch / 180.0 - 1.0).abs() < 0.01, "{}", pitch);
+
+        trombone.set_glottal_source_enabled(false);
+        let whisper = trombone.render(Duration::from_millis(100));
+        assert_eq!(PinkTrombone::detect_pitch(&whisper, SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn set_turbulence_points_rejects_invalid() {
+        let mut trombone = testing::trombone();
+        let point = |position, diameter| TurbulencePoint {
+            position,
+            diameter,
+            ..TurbulencePoint::default()
+        };
+        let rejected = trombone.set_turbulence_points(vec![
+            point(30.0, 0.5),
+            point(1.0, 0.5),
+            point(f32::NAN, 0.5),
+            point(40.0, 0.0),
+            point(30.0, -0.1),
+            point(100.0, 0.5),
+        ]);
+        assert_eq!(rejected, vec![1, 2, 4, 5]);
+        let positions: Vec<f32> = trombone
+            .turbulence_points()
+            .iter()
+            .map(|p| p.position)
+            .collect();
+        assert_eq!(positions, vec![30.0, 40.0]);
+    }
+
+    #[test]
+    fn true_peak_is_at_least_sample_peak() {
+        let mut trombone = testing::trombone();
+        let mut reference = trombone.clone();
+        let mut buffer = vec![0.0; 4800];
+        let mut expected = vec![0.0; 4800];
+        let true_peak = trombone.synthesize_true_peak(&mut buffer);
+        reference.synthesize(&mut expected);
+        assert_eq!(buffer, expected);
+
+        let sample_peak = buffer.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        assert!(true_peak >= sample_peak);
+        assert!(
+            true_peak < sample_peak * 1.5,
+            "{} {}",
+            true_peak,
+            sample_peak
+        );
+        assert_eq!(trombone.synthesize_true_peak(&mut []), 0.0);
+    }
+
+    #[test]
+    fn step_to_grid_glides_between_positions() {
+        let mut trombone = testing::trombone();
+        trombone.set_vowel_grid(vec![(12.9, 2.43), (27.2, 2.2), (-5.0, 9.0)]);
+        assert_eq!(
+            trombone.vowel_grid(),
+            &[(12.9, 2.43), (27.2, 2.2), (0.0, PinkTrombone::LOW_DIAMETER)]
+        );
+
+        let mut buffer = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+        for i in [1, 0] {
+            trombone.step_to_grid(i, Duration::from_millis(50));
+            assert_eq!(trombone.tongue_index(), trombone.vowel_grid()[i].0);
+            assert_eq!(trombone.tongue_diameter(), trombone.vowel_grid()[i].1);
+            let mut settled = trombone.clone();
+            settled.set_frozen(true);
+            // much faster than the default articulation speed allows
+            let mut buffer = vec![0.0; 3600];
+            trombone.synthesize(&mut buffer);
+            settled.synthesize(&mut buffer);
+            assert_eq!(
+                trombone.shaper.tract.diameter, settled.shaper.tract.diameter,
+                "{}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn debug_state_reports_settings() {
+        let mut trombone = testing::trombone();
+        trombone.set_target_frequency(220.0);
+        trombone.set_tongue_index(20.0);
+        let mut buffer = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+
+        let state = trombone.debug_state();
+        assert_eq!(state.lines().count(), 10, "{}", state);
+        assert!(
+            state.contains("sample rate: 48000 Hz (tract 96000 Hz)"),
+            "{}",
+            state
+        );
+        assert!(state.contains("time: 100ms"), "{}", state);
+        assert!(state.contains("target 220.00 Hz"), "{}", state);
+        assert!(state.contains("tongue: index 20.00"), "{}", state);
+        assert!(state.contains("turbulence points: 0"), "{}", state);
+    }
+
+    #[test]
+    fn owned_rng_matches_borrowed() {
+        let mut random = xorshift::XorShift128::new(SEED as u32);
+        let mut borrowed = PinkTrombone::new(SAMPLE_RATE, &mut random, SEED);
+        let rng = Box::new(xorshift::XorShift128::new(SEED as u32));
+        let mut owned = PinkTrombone::with_owned_rng(SAMPLE_RATE, rng, SEED);
+        let mut expected = vec![0.0; 4800];
+        let mut buffer = vec![0.0; 4800];
+        borrowed.synthesize(&mut expected);
+        owned.synthesize(&mut buffer);
+        assert_eq!(buffer, expected);
+
+        borrowed.set_noise_mode(NoiseMode::Live, &mut random);
+        assert!(owned.regenerate_noise(NoiseMode::Live));
+        assert!(!borrowed.regenerate_noise(NoiseMode::Live));
+        let mut clone = owned.clone();
+        borrowed.synthesize(&mut expected);
+        owned.synthesize(&mut buffer);
+        assert_eq!(buffer, expected);
+        let mut cloned = vec![0.0; 4800];
+        clone.synthesize(&mut cloned);
+        assert_eq!(cloned, buffer);
+
+        // cloning did not draw from the original's RNG,
+        // and the clone regenerates from its own
+        borrowed.set_noise_mode(NoiseMode::Live, &mut random);
+        assert!(owned.regenerate_noise(NoiseMode::Live));
+        assert!(clone.regenerate_noise(NoiseMode::Live));
+        borrowed.synthesize(&mut expected);
+        owned.synthesize(&mut buffer);
+        clone.synthesize(&mut cloned);
+        assert_eq!(buffer, expected);
+        assert_ne!(cloned, buffer);
+    }
+
+    #[test]
+    fn calibrate_gain_leaves_owned_rng_unchanged() {
+        let owned = || {
+            let rng = Box::new(xorshift::XorShift128::new(SEED as u32));
+            PinkTrombone::with_owned_rng(SAMPLE_RATE, rng, SEED)
+        };
+        let mut calibrated = owned();
+        let mut reference = owned();
+        assert!(calibrated.calibrate_gain(Duration::from_millis(100)) > 0.0);
+
+        let mut expected = vec![0.0; 4800];
+        let mut buffer = vec![0.0; 4800];
+        for _ in 0..2 {
+            assert!(calibrated.regenerate_noise(NoiseMode::Live));
+            assert!(reference.regenerate_noise(NoiseMode::Live));
+            calibrated.synthesize(&mut buffer);
+            reference.synthesize(&mut expected);
+            assert_eq!(buffer, expected);
+            // a clone made afterwards gets the same RNG either way
+            calibrated = calibrated.clone();
+            reference = reference.clone();
+        }
+    }
+
+    #[test]
+    fn subglottal_coupling_adds_low_resonances() {
+        let mut trombone = testing::trombone();
+        trombone.set_tongue_index(25.0);
+        trombone.set_tongue_diameter(3.0);
+        // a breathy glottis reflects less, and passes more into the tube
+        trombone.set_dynamic_glottal_reflection(true);
+        trombone.set_target_tenseness(0.3);
+        let mut buffer = vec![0.0; 4800];
+        trombone.synthesize(&mut buffer);
+        let response = |trombone: &mut PinkTrombone, coupling: bool, phase: f32| {
+            trombone.set_subglottal_coupling(coupling);
+            assert_eq!(trombone.subglottal_coupling(), coupling);
+            trombone.set_glottal_phase(phase);
+            trombone.impulse_response(4800)
+        };
+        // the tube only couples while the glottis is open
+        assert_eq!(
+            response(&mut trombone, true, 0.75),
+            response(&mut trombone, false, 0.75)
+        );
+
+        let coupled = response(&mut trombone, true, 0.25);
+        let uncoupled = response(&mut trombone, false, 0.25);
+        let magnitude = |response: &[f32], frequency: f32| {
+            let (mut re, mut im) = (0.0_f32, 0.0_f32);
+            for (i, x) in response.iter().enumerate() {
+                let phase = 2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE as f32;
+                re += x * phase.cos();
+                im += x * phase.sin();
+            }
+            (re * re + im * im).sqrt()
+        };
+        let change = |frequency: f32| {
+            (magnitude(&coupled, frequency) / magnitude(&uncoupled, frequency))
+                .log10()
+                .abs()
+                * 20.0
+        };
+        let low = (1..20)
+            .map(|k| change(k as f32 * 100.0))
+            .fold(0.0, f32::max);
+        assert!(low > 2.0, "{} dB", low);
+        assert!(coupled.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn zero_fricative_times_keep_output_finite() {
+        let mut trombone = testing::trombone();
+        trombone.set_fricative_attack(Duration::ZERO);
+        trombone.tract_mut().set_fricative_release_time(0.0);
+        assert!(trombone.tract().fricative_release_time() > 0.0);
+        let now = trombone.time().as_secs_f64();
+        trombone.set_turbulence_points(vec![TurbulencePoint {
+            diameter: 0.5,
+            position: 36.0,
+            start_time: now,
+            end_time: now + 0.05,
+        }]);
+        let output = trombone.render(Duration::from_millis(100));
+        assert!(output.iter().all(|x| x.is_finite()));
+        assert!(output.iter().any(|&x| x != 0.0));
+    }
+
+    #[test]
+    fn pcm_output_matches_synthesize() {
+        let mut trombone = testing::trombone();
+        let mut mulaw_trombone = trombone.clone();
+        let mut reference = trombone.clone();
+        let mut expected = vec![0.0; 1500];
+        reference.synthesize(&mut expected);
+
+        let mut linear = vec![0; 1500];
+        trombone.synthesize_i16(&mut linear);
+        let mut mulaw = vec![0; 1500];
+        mulaw_trombone.synthesize_mulaw(&mut mulaw);
+        for ((&x, &linear), &mulaw) in expected.iter().zip(&linear).zip(&mulaw) {
+            assert_eq!(linear, pcm::to_i16(x));
+            assert_eq!(mulaw, pcm::to_mulaw(x));
+        }
+    }
+
+    #[test]
+    fn aspiration_modulation_depth_pulses_noise() {
+        // energy in the open half of the glottal cycles over the closed half
+        let pulsing = |depth: f32| {
+            let mut trombone = testing::trombone();
+            trombone.set_frozen(true);
+            trombone.set_track_glottal_closures(true);
+            trombone.set_target_tenseness(0.2);
+            trombone.set_glottal_source_enabled(false);
+            trombone.set_aspiration_modulation(depth);
+            let output = trombone.render(Duration::from_secs(1));
+            let closures = trombone.take_glottal_closures();
+            let (mut open, mut closed) = (0.0, 0.0);
+            for pair in closures.windows(2) {
+                let cycle = &output[pair[0]..pair[1]];
+                let (first, second) = cycle.split_at(cycle.len() / 2);
+                open += first.iter().map(|x| x * x).sum::<f32>();
+                closed += second.iter().map(|x| x * x).sum::<f32>();
+            }
+            open / closed
+        };
+        let smooth = pulsing(0.0);
+        let default = pulsing(1.0);
+        let pulsed = pulsing(2.0);
+        assert!((smooth - 1.0).abs() < 0.1, "{}", smooth);
+        assert!(default > smooth + 0.05, "{} vs {}", default, smooth);
+        assert!(pulsed > default + 0.05, "{} vs {}", pulsed, default);
+    }
+
+    #[test]
+    fn unison_detune_spreads_harmonics() {
+        let frequency = math::semitone_to_hz(-12.0) as f64;
+        let render = |voices: usize| {
+            let mut trombone = noiseless();
+            trombone.set_frozen(true);
+            trombone.set_musical_note(-12.0);
+            let mut buffer = vec![0.0; SAMPLE_RATE as usize];
+            trombone.synthesize_unison(&mut buffer, voices, 50.0);
+            buffer.split_off(SAMPLE_RATE as usize / 4)
+        };
+        // amplitude of the `harmonic`-th harmonic of the main voice,
+        // detuned by `cents`
+        let magnitude = |signal: &[f32], harmonic: f64, cents: f64| {
+            magnitude_at(signal, harmonic * frequency * 2f64.powf(cents / 1200.0))
+        };
+        let single = render(1);
+        let chorus = render(3);
+        // the upper voice adds energy next to the harmonics,
+        // taking its share from the main voice
+        assert!(magnitude(&chorus, 4.0, 50.0) > 10.0 * magnitude(&single, 4.0, 50.0));
+        assert!(magnitude(&chorus, 4.0, 0.0) < 0.5 * magnitude(&single, 4.0, 0.0));
+
+        // averaging the voices keeps the level bounded
+        let rms = |signal: &[f32]| {
+            (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+        };
+        let peak = |signal: &[f32]| signal.iter().fold(0f32, |peak, x| peak.max(x.abs()));
+        assert!(rms(&chorus) > 0.0);
+        assert!(rms(&chorus) <= rms(&single));
+        assert!(peak(&chorus) <= peak(&single));
+    }
+
+    #[test]
+    fn piriform_branch_notches_spectrum() {
+        let frequency = math::semitone_to_hz(-24.0) as f64;
+        let render = |enabled: bool| {
+            let mut trombone = noiseless();
+            trombone.set_frozen(true);
+            trombone.set_musical_note(-24.0);
+            trombone.set_piriform_enabled(enabled);
+            let mut output = trombone.render(Duration::from_secs(1));
+            output.split_off(SAMPLE_RATE as usize / 4)
+        };
+        // energy of the harmonics within `band`
+        let energy = |signal: &[f32], band: std::ops::Range<f64>| {
+            (1..)
+                .map(|k| k as f64 * frequency)
+                .skip_while(|hz| *hz < band.start)
+                .take_while(|hz| *hz < band.end)
+                .map(|hz| magnitude_at(signal, hz).powi(2))
+                .sum::<f64>()
+        };
+        let without = render(false);
+        let with = render(true);
+        let notch = 4500.0..5100.0;
+        assert!(energy(&with, notch.clone()) < 0.1 * energy(&without, notch));
+        // lower formants are barely affected
+        let ratio = energy(&with, 500.0..1500.0) / energy(&without, 500.0..1500.0);
+        assert!((0.8..1.25).contains(&ratio), "{}", ratio);
+    }
+
+    #[test]
+    fn reset_rearms_startup_fade() {
+        let mut trombone = testing::trombone();
+        trombone.set_startup_fade(Duration::from_millis(10));
+        let output = trombone.render(Duration::from_millis(100));
+        assert_eq!(output[0], 0.0);
+
+        let mut reference = trombone.clone();
+        trombone.reset();
+        reference.reset();
+        reference.set_startup_fade(Duration::ZERO);
+        let faded = trombone.render(Duration::from_millis(20));
+        let expected = reference.render(Duration::from_millis(20));
+        let len = SAMPLE_RATE as usize / 100;
+        for (i, (x, y)) in faded.iter().zip(&expected).enumerate() {
+            let gain = (i as f32 / len as f32).min(1.0);
+            assert!((x - y * gain).abs() < 1e-6, "{}: {} vs {}", i, x, y);
+        }
+    }
+
+    #[test]
+    fn intensity_timing_is_independent_of_sample_rate() {
+        let sample_rate = 44100;
+        let mut random = xorshift::XorShift128::new(SEED.into());
+        let mut trombone = PinkTrombone::new(sample_rate, &mut random, SEED);
+        let mut buf = vec![0.0; sample_rate as usize / 20];
+
+        trombone.set_intensity(0.0);
+        trombone.synthesize(&mut buf);
+        // 0.13 per 512-sample block at 48kHz, for 50ms
+        let attack = 0.13 * 48000.0 / 512.0 * 0.05;
+        assert!((trombone.intensity() - attack).abs() < 1e-4);
+
+        trombone.set_always_voice(false);
+        trombone.set_intensity(1.0);
+        trombone.synthesize(&mut buf);
+        let release = 0.05 * 48000.0 / 512.0 * 0.05;
+        assert!((trombone.intensity() - (1.0 - release)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn render_loopable_wraps_around_smoothly() {
+        let mut trombone = noiseless();
+        trombone.set_vibrato_enabled(false);
+        trombone.set_tenseness_drift(false);
+        trombone.set_target_frequency(137.0);
+        trombone.warmup(Duration::from_millis(500));
+        let buf = trombone.render_loopable(Duration::from_millis(100));
+
+        let period = SAMPLE_RATE as f64 / 137.0;
+        let periods = buf.len() as f64 / period;
+        assert!(periods > 1.0);
+        assert!(
+            (periods - periods.round()).abs() * period <= 0.5,
+            "{}",
+            periods
+        );
+        let peak = buf.iter().fold(0.0_f32, |peak, x| peak.max(x.abs()));
+        let wrap = (buf[0] - buf[buf.len() - 1]).abs();
+        assert!(wrap < 0.05 * peak, "{} vs {}", wrap, peak);
+    }
+
+    #[test]
+    fn stereo_without_width_is_mono() {
+        let mut trombone = testing::trombone();
+        trombone.set_decimation(DecimationMode::Average);
+        let mut mono = trombone.clone();
+        let mut expected = vec![0.0; SAMPLE_RATE as usize / 4];
+        mono.synthesize(&mut expected);
+        let mut left = vec![0.0; expected.len()];
+        let mut right = vec![0.0; expected.len()];
+        trombone.synthesize_stereo(&mut left, &mut right, 0.0);
+        assert_eq!(left, right);
+        assert_eq!(left, expected);
+    }
+
+    #[test]
+    fn stereo_pans_nasal_output_right() {
+        let mut trombone = noiseless();
+        trombone.set_velum_open(true);
+        trombone.set_constriction(Some(Constriction {
+            index: Tract::LIP_START as f64 + 2.0,
+            diameter: 0.0,
+        }));
+        trombone.warmup(Duration::from_millis(500));
+        let mut left = vec![0.0; SAMPLE_RATE as usize / 4];
+        let mut right = vec![0.0; left.len()];
+        trombone.synthesize_stereo(&mut left, &mut right, 0.5);
+        let energy = |buf: &[f32]| buf.iter().map(|&x| x as f64 * x as f64).sum::<f64>();
+        assert!(energy(&right) > 4.0 * energy(&left));
     }
 }
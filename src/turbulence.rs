@@ -1,8 +1,17 @@
+use crate::tract::Tract;
+
+/// A point of turbulence noise along the tract, e.g. a fricative.
+///
+/// `start_time` and `end_time` are in seconds of synthesis time, see
+/// `PinkTrombone::time`. Since 0.3 they are `f64`, rather than `f32`,
+/// so that they stay precise in long sessions: convert `f32` times
+/// with `f64::from`.
+#[derive(Clone)]
 pub struct TurbulencePoint {
     pub diameter: f32,
     pub position: f32,
-    pub start_time: f32,
-    pub end_time: f32,
+    pub start_time: f64,
+    pub end_time: f64,
 }
 
 impl Default for TurbulencePoint {
@@ -11,7 +20,171 @@ impl Default for TurbulencePoint {
             diameter: 0.0,
             position: 0.0,
             start_time: 0.0,
-            end_time: f32::NAN,
+            end_time: f64::NAN,
+        }
+    }
+}
+
+impl TurbulencePoint {
+    /// Whether the point lies within the tract and has a non-negative
+    /// diameter and a start time. Points not making any noise are valid.
+    pub fn is_valid(&self) -> bool {
+        (2.0..=Tract::N as f32).contains(&self.position)
+            && self.diameter >= 0.0
+            && self.diameter.is_finite()
+            && self.start_time.is_finite()
+            && !self.end_time.is_infinite()
+    }
+}
+
+/// How turbulence noise injected at a fractional position along the tract
+/// is spread over the neighbouring cells.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TurbulenceDistribution {
+    #[default]
+    /// Linear interpolation between the two nearest cells.
+    TwoPoint,
+    /// Quadratic B-spline over the three nearest cells. The weights change
+    /// smoothly as the position moves, reducing artifacts on moving fricatives.
+    ThreePoint,
+}
+
+impl TurbulenceDistribution {
+    /// Returns the index of the first cell receiving noise
+    /// and the weights of it and the following cells, summing to 1.
+    pub fn weights(self, position: f64) -> (i64, [f64; 3]) {
+        match self {
+            TurbulenceDistribution::TwoPoint => {
+                let i = position.floor() as i64;
+                let delta = position - i as f64;
+                (i + 1, [1.0 - delta, delta, 0.0])
+            }
+            TurbulenceDistribution::ThreePoint => {
+                // centered on the same point the two-point scheme interpolates at
+                let center = position + 1.0;
+                let k = center.round() as i64;
+                let delta = center - k as f64;
+                (
+                    k - 1,
+                    [
+                        0.5 * (0.5 - delta) * (0.5 - delta),
+                        0.75 - delta * delta,
+                        0.5 * (0.5 + delta) * (0.5 + delta),
+                    ],
+                )
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{f64::consts::PI, time::Duration};
+
+    use crate::{
+        noise::NoiseSource,
+        testing::{ConstantNoise, SAMPLE_RATE},
+        NoiseMode, PinkTrombone,
+    };
+
+    /// period of `ToneNoise` in tract samples
+    const PERIOD: usize = 48;
+
+    /// "Noise" in 0..1 that is a 2 kHz tone at the tract sample rate,
+    /// so that the level of the frication in the output is easy to follow.
+    struct ToneNoise(usize);
+
+    impl NoiseSource<f64> for ToneNoise {
+        fn noise(&mut self) -> f64 {
+            self.0 += 1;
+            0.5 + 0.5 * (2.0 * PI * self.0 as f64 / PERIOD as f64).sin()
+        }
+    }
+
+    /// Level of a fricative moving along the tract at 16 cells per second,
+    /// sampled every tone period.
+    fn moving_fricative_level(distribution: TurbulenceDistribution) -> Vec<f64> {
+        let mut trombone = PinkTrombone::with_noise_sources(
+            SAMPLE_RATE,
+            &mut ConstantNoise(0.5),
+            &mut ToneNoise(0),
+            0,
+            NoiseMode::Looped(PERIOD * 1000),
+        );
+        trombone.set_always_voice(false);
+        trombone.set_intensity(0.0);
+        trombone.set_turbulence_distribution(distribution);
+        trombone.set_block_size(PERIOD / 2);
+        let start = trombone.time().as_secs_f64();
+        let mut output = Vec::new();
+        for n in 0..2 * SAMPLE_RATE as usize / (PERIOD / 2) {
+            let time = trombone.time().as_secs_f64();
+            trombone.set_turbulence_points(vec![TurbulencePoint {
+                diameter: 0.5,
+                position: 12.0 + 16.0 * (time - start) as f32,
+                start_time: start,
+                end_time: f64::NAN,
+            }]);
+            let block = trombone.render(Duration::from_secs_f64(
+                (PERIOD / 2) as f64 / SAMPLE_RATE as f64,
+            ));
+            if n > 0 {
+                output.push(block.iter().map(|&x| (x * x) as f64).sum::<f64>().sqrt());
+            }
+        }
+        output
+    }
+
+    /// Fraction of the energy of `signal` above `freq`,
+    /// relative to the sample rate.
+    fn high_frequency_fraction(signal: &[f64], freq: f64) -> f64 {
+        let len = signal.len();
+        let (mut high, mut total) = (0.0, 0.0);
+        for bin in 1..len / 2 {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, &x) in signal.iter().enumerate() {
+                let phase = 2.0 * PI * (bin * n) as f64 / len as f64;
+                re += x * phase.cos();
+                im -= x * phase.sin();
+            }
+            let power = re * re + im * im;
+            total += power;
+            if bin as f64 / len as f64 > freq {
+                high += power;
+            }
+        }
+        high / total
+    }
+
+    #[test]
+    fn weights_sum_to_one() {
+        for distribution in [
+            TurbulenceDistribution::TwoPoint,
+            TurbulenceDistribution::ThreePoint,
+        ] {
+            for n in 0..100 {
+                let (_, weights) = distribution.weights(2.0 + n as f64 * 0.37);
+                assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn three_point_reduces_positional_artifacts() {
+        // at and above the rate cells are crossed at, the level only
+        // changes with the corners of the interpolation kernel
+        let freq = 16.0 * (PERIOD / 2) as f64 / SAMPLE_RATE as f64;
+        let two_point = moving_fricative_level(TurbulenceDistribution::TwoPoint);
+        let three_point = moving_fricative_level(TurbulenceDistribution::ThreePoint);
+        let two_point_artifacts = high_frequency_fraction(&two_point, freq);
+        let three_point_artifacts = high_frequency_fraction(&three_point, freq);
+        assert!(
+            three_point_artifacts < two_point_artifacts * 0.5,
+            "{} {}",
+            three_point_artifacts,
+            two_point_artifacts
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{glottal_source::GlottalSource, glottis::Glottis};
+
+/// Extra glottal sources following the main glottis at detuned pitches.
+#[derive(Clone)]
+pub struct Unison {
+    voices: Vec<Voice>,
+    detune_cents: f32,
+}
+
+#[derive(Clone)]
+struct Voice {
+    glottis: Glottis,
+    frequency_ratio: f32,
+}
+
+impl Unison {
+    pub fn new() -> Unison {
+        Unison {
+            voices: Vec::new(),
+            detune_cents: 0.0,
+        }
+    }
+
+    /// Number of voices including the main glottis.
+    pub fn len(&self) -> usize {
+        self.voices.len() + 1
+    }
+
+    /// Sets up `extra_voices` voices besides the main one, spread evenly
+    /// over `-detune_cents..detune_cents` with evenly spread phases.
+    /// Voices are only rebuilt when their number changes,
+    /// with the glottal source of `leader`.
+    pub fn configure(
+        &mut self,
+        extra_voices: usize,
+        detune_cents: f32,
+        leader: &Glottis,
+        sample_rate: u32,
+    ) {
+        if extra_voices != self.voices.len() {
+            self.voices = (0..extra_voices)
+                .map(|k| {
+                    // extra voices only add voicing: aspiration noise
+                    // comes from the main glottis
+                    let mut glottis = Glottis::without_aspiration(sample_rate, k as u16 + 1);
+                    glottis.set_phase((k + 1) as f32 / (extra_voices + 1) as f32);
+                    glottis.set_source(leader.source().box_clone());
+                    Voice {
+                        glottis,
+                        frequency_ratio: 1.0,
+                    }
+                })
+                .collect();
+            self.detune_cents = f32::NAN;
+        }
+        if detune_cents != self.detune_cents {
+            let len = self.voices.len();
+            for (k, voice) in self.voices.iter_mut().enumerate() {
+                let spread = if len == 1 {
+                    1.0
+                } else {
+                    2.0 * k as f32 / (len - 1) as f32 - 1.0
+                };
+                voice.frequency_ratio = 2.0_f32.powf(spread * detune_cents / 1200.0);
+            }
+            self.detune_cents = detune_cents;
+        }
+    }
+
+    /// Replaces the glottal source of all the extra voices.
+    pub fn set_source(&mut self, source: &dyn GlottalSource) {
+        for voice in &mut self.voices {
+            voice.glottis.set_source(source.box_clone());
+        }
+    }
+
+    /// Call before `leader` adjusts its parameters for the block.
+    pub fn adjust_parameters(&mut self, leader: &Glottis, delta_time: f32) {
+        for voice in &mut self.voices {
+            voice.glottis.follow(leader, voice.frequency_ratio);
+            voice.glottis.adjust_parameters(delta_time);
+        }
+    }
+
+    /// Sum of the glottal pulses of the extra voices.
+    pub fn step(&mut self, lambda: f32) -> f32 {
+        self.voices
+            .iter_mut()
+            .map(|voice| voice.glottis.step_components(lambda).0)
+            .sum()
+    }
+}